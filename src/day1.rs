@@ -1,11 +1,247 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead};
 use aoc_runner_derive::{aoc, aoc_generator};
-use lazy_static::lazy_static;
-use regex::Regex;
 
-static PATTERN: &str = r".*(one|two|three|four|five|six|seven|eight|nine|\d)";
-lazy_static! {
-    static ref FIRST: Regex = Regex::new(&PATTERN[2..]).unwrap();
-    static ref LAST: Regex = Regex::new(PATTERN).unwrap();
+pub static ENGLISH: &[&str] = &["one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+pub static ENGLISH_TEENS: &[&str] = &[
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen",
+    "nineteen", "twenty",
+];
+pub static FRENCH: &[&str] = &["un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf"];
+pub static GERMAN: &[&str] = &["eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun"];
+pub static ROMAN: &[&str] = &[
+    "i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix", "x",
+    "xi", "xii", "xiii", "xiv", "xv", "xvi", "xvii", "xviii", "xix", "xx",
+];
+
+/// Words recognised as digits by the calibration scanner, with their values.
+#[derive(Debug, Clone)]
+pub struct DigitVocabulary {
+    words: Vec<(Vec<u8>, u64)>,
+}
+
+impl DigitVocabulary {
+    /// Vocabulary recognising only the ASCII digits `0` to `9`.
+    pub fn digits() -> Self {
+        Self {
+            words: (0..10).map(|d| (vec![b'0' + d as u8], d)).collect(),
+        }
+    }
+
+    /// Vocabulary used by the puzzle: ASCII digits and English `one` to `nine`.
+    pub fn english() -> Self {
+        Self::digits().with_words(1, ENGLISH)
+    }
+
+    pub fn with_word(mut self, word: &str, value: u64) -> Self {
+        self.words.push((word.as_bytes().to_vec(), value));
+        self
+    }
+
+    /// Adds `words` with consecutive values, the first one being valued `first`.
+    pub fn with_words(self, first: u64, words: &[&str]) -> Self {
+        words.iter()
+            .zip(first..)
+            .fold(self, |vocabulary, (word, value)| vocabulary.with_word(word, value))
+    }
+
+    /// Leftmost match, the longest one winning ties.
+    pub fn first(&self, line: &str) -> Option<u64> {
+        self.first_in(line.as_bytes())
+    }
+
+    /// Rightmost match, the longest one winning ties.
+    pub fn last(&self, line: &str) -> Option<u64> {
+        self.last_in(line.as_bytes())
+    }
+
+    /// Concatenation of the first and last values, e.g. `1` and `12` give `112`. `None` if the
+    /// line has no digit or the value doesn't fit in a `u64`.
+    pub fn calibration_value(&self, line: &str) -> Option<u64> {
+        self.calibration_value_in(line.as_bytes()).ok()
+    }
+
+    pub fn calibrate_line(&self, line: &str) -> Result<u64, MissingReason> {
+        self.calibrate_bytes(line.as_bytes())
+    }
+
+    fn first_in(&self, line: &[u8]) -> Option<u64> {
+        (0..line.len()).find_map(|start| self.longest(|word| line[start..].starts_with(word)))
+    }
+
+    fn last_in(&self, line: &[u8]) -> Option<u64> {
+        (1..=line.len()).rev().find_map(|end| self.longest(|word| line[..end].ends_with(word)))
+    }
+
+    fn calibration_value_in(&self, line: &[u8]) -> Result<u64, MissingReason> {
+        let (Some(first), Some(last)) = (self.first_in(line), self.last_in(line)) else {
+            return Err(MissingReason::NoDigit);
+        };
+        10u64.checked_pow(last.checked_ilog10().unwrap_or(0) + 1)
+            .and_then(|shift| first.checked_mul(shift))
+            .and_then(|value| value.checked_add(last))
+            .ok_or(MissingReason::Overflow)
+    }
+
+    fn calibrate_bytes(&self, line: &[u8]) -> Result<u64, MissingReason> {
+        if line.trim_ascii().is_empty() {
+            Err(MissingReason::EmptyLine)
+        } else {
+            self.calibration_value_in(line)
+        }
+    }
+
+    fn longest(&self, matches: impl Fn(&[u8]) -> bool) -> Option<u64> {
+        self.words.iter()
+            .filter(|(word, _)| matches(word))
+            .max_by_key(|(word, _)| word.len())
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Why a line has no calibration value, or why the sum stopped at it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MissingReason {
+    EmptyLine,
+    NoDigit,
+    /// The line value doesn't fit in a `u64`.
+    Overflow,
+    /// Adding the line value overflows the total, in any mode.
+    TotalOverflow,
+}
+
+impl Display for MissingReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingReason::EmptyLine => write!(f, "empty line"),
+            MissingReason::NoDigit => write!(f, "no digit"),
+            MissingReason::Overflow => write!(f, "value doesn't fit in 64 bits"),
+            MissingReason::TotalOverflow => write!(f, "total doesn't fit in 64 bits"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CalibrationError {
+    pub line: usize,
+    pub reason: MissingReason,
+}
+
+impl Display for CalibrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for CalibrationError {}
+
+/// Whether lines without calibration value abort the sum or are skipped.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    Strict,
+    Lenient,
+}
+
+/// Calibration outcome of a single line, numbered from 1.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct LineCalibration {
+    pub line: usize,
+    pub value: Result<u64, MissingReason>,
+}
+
+impl LineCalibration {
+    fn contribution(&self, mode: Mode) -> Result<u64, CalibrationError> {
+        match (self.value, mode) {
+            (Ok(value), _) => Ok(value),
+            (Err(_), Mode::Lenient) => Ok(0),
+            (Err(reason), Mode::Strict) => Err(CalibrationError { line: self.line, reason }),
+        }
+    }
+
+    fn add_to(&self, total: u64, mode: Mode) -> Result<u64, CalibrationError> {
+        total.checked_add(self.contribution(mode)?)
+            .ok_or(CalibrationError { line: self.line, reason: MissingReason::TotalOverflow })
+    }
+}
+
+fn sum<'a>(lines: impl IntoIterator<Item=&'a LineCalibration>, mode: Mode) -> Result<u64, CalibrationError> {
+    lines.into_iter().try_fold(0, |total, line| line.add_to(total, mode))
+}
+
+pub fn calibrate_lines<'a, I>(lines: I, vocabulary: &'a DigitVocabulary) -> impl Iterator<Item=LineCalibration> + 'a
+where
+    I: IntoIterator<Item=&'a str>,
+    I::IntoIter: 'a,
+{
+    lines.into_iter().zip(1..).map(|(line, number)| LineCalibration {
+        line: number,
+        value: vocabulary.calibrate_line(line),
+    })
+}
+
+pub fn calibrate(input: &[String], vocabulary: &DigitVocabulary, mode: Mode) -> Result<u64, CalibrationError> {
+    calibrate_lines(input.iter().map(String::as_str), vocabulary)
+        .try_fold(0, |total, line| line.add_to(total, mode))
+}
+
+/// Streams lines out of `reader` through a single reused buffer, so memory use is bounded by the
+/// longest line rather than the document size. Strict mode failures surface as `InvalidData`.
+pub fn calibrate_reader<R: BufRead>(mut reader: R, vocabulary: &DigitVocabulary, mode: Mode) -> io::Result<u64> {
+    let mut buffer = Vec::new();
+    let mut total = 0;
+    for number in 1.. {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        let line = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let calibration = LineCalibration { line: number, value: vocabulary.calibrate_bytes(line) };
+        total = calibration.add_to(total, mode)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    }
+    Ok(total)
+}
+
+/// Per-line audit of a calibration document.
+#[derive(Debug, Clone)]
+pub struct CalibrationReport {
+    pub lines: Vec<LineCalibration>,
+}
+
+impl CalibrationReport {
+    pub fn new(input: &[String], vocabulary: &DigitVocabulary) -> Self {
+        Self {
+            lines: calibrate_lines(input.iter().map(String::as_str), vocabulary).collect(),
+        }
+    }
+
+    pub fn total(&self, mode: Mode) -> Result<u64, CalibrationError> {
+        sum(&self.lines, mode)
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item=CalibrationError> + '_ {
+        self.lines.iter().filter_map(|line| match line.value {
+            Ok(_) => None,
+            Err(reason) => Some(CalibrationError { line: line.line, reason }),
+        })
+    }
+}
+
+impl Display for CalibrationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line.value {
+                Ok(value) => writeln!(f, "line {}: {}", line.line, value)?,
+                Err(reason) => writeln!(f, "line {}: skipped ({})", line.line, reason)?,
+            }
+        }
+        match self.total(Mode::Lenient) {
+            Ok(total) => write!(f, "total: {total}"),
+            Err(err) => write!(f, "total: {err}"),
+        }
+    }
 }
 
 #[aoc_generator(day1)]
@@ -15,49 +251,17 @@ pub fn input_generator(input: &str) -> Vec<String> {
 
 #[aoc(day1, part1)]
 pub fn solve_part1(input: &[String]) -> u64 {
-    input
-        .iter()
-        .map(|line| {
-            let digits = line
-                .bytes()
-                .filter(|b| b.is_ascii_digit())
-                .map(|b| b - b'0')
-                .collect::<Vec<_>>();
-            ((digits.first().unwrap() * 10) + (digits.last().unwrap())) as u64
-        })
-        .sum()
+    calibrate(input, &DigitVocabulary::digits(), Mode::Strict).unwrap()
 }
 
 #[aoc(day1, part2)]
 pub fn solve_part2(input: &[String]) -> u64 {
-    input
-        .iter()
-        .map(|line| extract(line, &FIRST) * 10 + extract(line, &LAST))
-        .sum()
-}
-
-fn convert(raw: &str) -> u64 {
-    match raw {
-        "one" => 1,
-        "two" => 2,
-        "three" => 3,
-        "four" => 4,
-        "five" => 5,
-        "six" => 6,
-        "seven" => 7,
-        "eight" => 8,
-        "nine" => 9,
-        raw => (raw.as_bytes()[0] - b'0') as u64
-    }
-}
-
-fn extract(line: &str, pattern: &Regex) -> u64 {
-    convert(pattern.captures_iter(line).next().unwrap().get(1).unwrap().as_str())
+    calibrate(input, &DigitVocabulary::english(), Mode::Strict).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{input_generator, solve_part1, solve_part2};
+    use super::*;
 
     static INPUT1: &str = "\
 1abc2
@@ -83,4 +287,69 @@ zoneight234
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator(INPUT2)), 281);
     }
+
+    #[test]
+    fn calibrate_with_other_vocabularies() {
+        let french = DigitVocabulary::digits().with_words(1, FRENCH);
+        assert_eq!(calibrate(&input_generator("deuxtrois\nx7neufy"), &french, Mode::Strict), Ok(23 + 79));
+
+        let german = DigitVocabulary::digits().with_words(1, GERMAN);
+        assert_eq!(calibrate(&input_generator("fünfzehn3"), &german, Mode::Strict), Ok(53));
+
+        let roman = DigitVocabulary::digits().with_words(1, ROMAN);
+        assert_eq!(roman.calibration_value("xiv-viii"), Some(148));
+
+        let teens = DigitVocabulary::english().with_words(10, ENGLISH_TEENS);
+        assert_eq!(teens.calibration_value("seventeen2eighteen"), Some(1718));
+        assert_eq!(teens.calibration_value("nonumber"), None);
+
+        let big = DigitVocabulary::digits().with_word("big", u64::MAX / 2);
+        assert_eq!(big.calibration_value("big1"), None);
+
+        let huge = DigitVocabulary::digits().with_word("huge", 10u64.pow(18));
+        assert_eq!(calibrate(&input_generator("1huge"), &huge, Mode::Strict), Ok(11 * 10u64.pow(18)));
+        assert_eq!(
+            calibrate(&input_generator("1huge\n1huge"), &huge, Mode::Strict),
+            Err(CalibrationError { line: 2, reason: MissingReason::TotalOverflow }),
+        );
+    }
+
+    #[test]
+    fn report_dirty_lines() {
+        let input = input_generator("1abc2\nnothing\n\ntreb7uchet");
+        let report = CalibrationReport::new(&input, &DigitVocabulary::digits());
+
+        assert_eq!(report.total(Mode::Lenient), Ok(89));
+        assert_eq!(report.total(Mode::Strict), Err(CalibrationError { line: 2, reason: MissingReason::NoDigit }));
+        assert_eq!(report.skipped().map(|e| e.reason).collect::<Vec<_>>(), [MissingReason::NoDigit, MissingReason::EmptyLine]);
+        assert_eq!(report.to_string(), "\
+line 1: 12
+line 2: skipped (no digit)
+line 3: skipped (empty line)
+line 4: 77
+total: 89");
+
+        let huge = DigitVocabulary::digits().with_word("huge", 10u64.pow(18));
+        let input = input_generator("big\n1huge\n1huge");
+        let report = CalibrationReport::new(&input, &huge.clone().with_word("big", u64::MAX / 2));
+        assert_eq!(report.lines[0].value, Err(MissingReason::Overflow));
+        assert_eq!(report.lines[1].value, Ok(11 * 10u64.pow(18)));
+        assert_eq!(calibrate(&input, &huge, Mode::Lenient), Err(CalibrationError { line: 3, reason: MissingReason::TotalOverflow }));
+        assert!(report.to_string().ends_with("total: line 3: total doesn't fit in 64 bits"));
+    }
+
+    #[test]
+    fn calibrate_reader_match_example() {
+        let vocabulary = DigitVocabulary::english();
+        assert_eq!(calibrate_reader(INPUT2.as_bytes(), &vocabulary, Mode::Strict).unwrap(), 281);
+
+        let crlf = INPUT2.replace('\n', "\r\n");
+        assert_eq!(calibrate_reader(crlf.as_bytes(), &vocabulary, Mode::Strict).unwrap(), 281);
+
+        let dirty = "two1nine\n\nxyz\n";
+        assert_eq!(calibrate_reader(dirty.as_bytes(), &vocabulary, Mode::Lenient).unwrap(), 29);
+        let err = calibrate_reader(dirty.as_bytes(), &vocabulary, Mode::Strict).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "line 2: empty line");
+    }
 }
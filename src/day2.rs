@@ -1,40 +1,164 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use aoc_runner_derive::{aoc, aoc_generator};
 
+pub mod probability;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    UnknownColor(String),
+    InvalidCount(String),
+    InvalidGroup(String),
+    InvalidHeader(String),
+    InvalidSpec(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownColor(color) => write!(f, "unknown color `{color}`"),
+            ParseError::InvalidCount(count) => write!(f, "invalid cube count `{count}`"),
+            ParseError::InvalidGroup(group) => write!(f, "expected `<count> <color>`, got `{group}`"),
+            ParseError::InvalidHeader(header) => write!(f, "expected `Game <id>`, got `{header}`"),
+            ParseError::InvalidSpec(spec) => write!(f, "expected `<color>=<count>`, got `{spec}`"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Colour names interned to the indices used by `CubeSet`.
+///
+/// An open palette learns colours as they are parsed, a closed one rejects unknown colours.
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    colors: Vec<String>,
+    closed: bool,
+}
+
+impl Palette {
+    pub fn open() -> Self {
+        Self::default()
+    }
+
+    pub fn closed(colors: &[&str]) -> Self {
+        Self {
+            colors: colors.iter().map(|c| c.to_string()).collect(),
+            closed: true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn colors(&self) -> &[String] {
+        &self.colors
+    }
+
+    pub fn index(&self, color: &str) -> Option<usize> {
+        self.colors.iter().position(|c| c == color)
+    }
+
+    pub fn intern(&mut self, color: &str) -> Result<usize, ParseError> {
+        match self.index(color) {
+            Some(index) => Ok(index),
+            None if self.closed => Err(ParseError::UnknownColor(color.to_string())),
+            None => {
+                self.colors.push(color.to_string());
+                Ok(self.colors.len() - 1)
+            }
+        }
+    }
+}
+
+/// Cube counts indexed by palette colour, colours past the end counting zero cubes.
 #[derive(Debug, Clone, Default)]
 pub struct CubeSet {
-    blue: usize,
-    green: usize,
-    red: usize,
+    counts: Vec<usize>,
 }
 
+impl PartialEq for CubeSet {
+    fn eq(&self, other: &Self) -> bool {
+        (0..self.counts.len().max(other.counts.len())).all(|color| self.count(color) == other.count(color))
+    }
+}
+
+impl Eq for CubeSet {}
+
 impl CubeSet {
-    fn from_str(raw_set: &str) -> Self {
+    pub fn parse(raw_set: &str, palette: &mut Palette) -> Result<Self, ParseError> {
         let mut set = CubeSet::default();
         for group in raw_set.split(", ") {
-            let (count, color) = group.split_once(' ').unwrap();
-            match color {
-                "blue" => set.blue = count.parse().unwrap(),
-                "green" => set.green = count.parse().unwrap(),
-                "red" => set.red = count.parse().unwrap(),
-                _ => unreachable!(),
+            let (count, color) = group.split_once(' ')
+                .ok_or_else(|| ParseError::InvalidGroup(group.to_string()))?;
+            let count = count.parse()
+                .map_err(|_| ParseError::InvalidCount(count.to_string()))?;
+            set.set(palette.intern(color)?, count);
+        }
+        Ok(set)
+    }
+
+    /// Parses `<color>=<count>` items, as given on the command line or one per line in a bag
+    /// config file. Blank lines and `#` comments are ignored, colours must already be in the
+    /// palette.
+    pub fn parse_spec<'a>(items: impl IntoIterator<Item=&'a str>, palette: &Palette) -> Result<Self, ParseError> {
+        let mut set = CubeSet::default();
+        for item in items {
+            let item = item.split('#').next().unwrap().trim();
+            if item.is_empty() {
+                continue;
             }
+            let (color, count) = item.split_once('=')
+                .ok_or_else(|| ParseError::InvalidSpec(item.to_string()))?;
+            let count = count.trim().parse()
+                .map_err(|_| ParseError::InvalidCount(count.trim().to_string()))?;
+            let color = color.trim();
+            let index = palette.index(color).ok_or_else(|| ParseError::UnknownColor(color.to_string()))?;
+            set.set(index, count);
         }
-        set
+        Ok(set)
     }
 
-    fn smaller_than(&self, other: &Self) -> bool {
-        self.blue <= other.blue && self.green <= other.green && self.red <= other.red
+    pub fn count(&self, color: usize) -> usize {
+        self.counts.get(color).copied().unwrap_or(0)
     }
 
-    fn power(&self) -> usize {
-        self.blue * self.green * self.red
+    pub fn set(&mut self, color: usize, count: usize) {
+        if self.counts.len() <= color {
+            self.counts.resize(color + 1, 0);
+        }
+        self.counts[color] = count;
+    }
+
+    pub fn smaller_than(&self, other: &Self) -> bool {
+        self.counts.iter().enumerate().all(|(color, count)| *count <= other.count(color))
+    }
+
+    /// Product of the counts of every colour of the palette.
+    pub fn power(&self, palette: &Palette) -> usize {
+        (0..palette.len()).map(|color| self.count(color)).product()
     }
 
-    fn max(&self, other: &Self) -> Self {
+    /// Renders the set in the puzzle notation, e.g. `3 blue, 4 red`.
+    pub fn describe(&self, palette: &Palette) -> String {
+        self.counts.iter()
+            .zip(palette.colors())
+            .filter(|(count, _)| **count > 0)
+            .map(|(count, color)| format!("{count} {color}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn max(&self, other: &Self) -> Self {
         Self {
-            blue: self.blue.max(other.blue),
-            green: self.green.max(other.green),
-            red: self.red.max(other.red),
+            counts: (0..self.counts.len().max(other.counts.len()))
+                .map(|color| self.count(color).max(other.count(color)))
+                .collect(),
         }
     }
 }
@@ -46,46 +170,97 @@ pub struct Game {
 }
 
 impl Game {
-    fn from_str(raw_game: &str) -> Self {
-        let (header, raw_sets) = raw_game.split_once(": ").unwrap();
-        let id = header.split_once(' ').unwrap().1.parse().unwrap();
-        let sets = raw_sets.split("; ").map(CubeSet::from_str).collect();
+    pub fn parse(raw_game: &str, palette: &mut Palette) -> Result<Self, ParseError> {
+        let (header, raw_sets) = raw_game.split_once(": ")
+            .ok_or_else(|| ParseError::InvalidHeader(raw_game.to_string()))?;
+        let id = header.strip_prefix("Game ")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| ParseError::InvalidHeader(header.to_string()))?;
+        let sets = raw_sets.split("; ")
+            .map(|raw_set| CubeSet::parse(raw_set, palette))
+            .collect::<Result<_, _>>()?;
 
-        Self {
+        Ok(Self {
             id,
             cube_sets: sets,
-        }
+        })
     }
 
-    fn max_set(&self) -> CubeSet {
-        self.cube_sets.iter().cloned().reduce(|a, b| a.max(&b)).unwrap()
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn cube_sets(&self) -> &[CubeSet] {
+        &self.cube_sets
+    }
+
+    pub fn max_set(&self) -> CubeSet {
+        self.cube_sets.iter().fold(CubeSet::default(), |a, b| a.max(b))
+    }
+
+    /// Index of the first draw that doesn't fit in `bag`.
+    pub fn first_violation(&self, bag: &CubeSet) -> Option<usize> {
+        self.cube_sets.iter().position(|set| !set.smaller_than(bag))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    pub game: usize,
+    pub draw: usize,
+    pub set: CubeSet,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Feasibility {
+    pub feasible: Vec<usize>,
+    pub infeasible: Vec<Violation>,
+    /// Smallest bag that would make every game feasible.
+    pub minimal_bag: CubeSet,
+}
+
+pub fn feasibility(games: &[Game], bag: &CubeSet) -> Feasibility {
+    let mut report = Feasibility {
+        feasible: vec![],
+        infeasible: vec![],
+        minimal_bag: CubeSet::default(),
+    };
+    for game in games {
+        match game.first_violation(bag) {
+            Some(draw) => report.infeasible.push(Violation {
+                game: game.id,
+                draw,
+                set: game.cube_sets[draw].clone(),
+            }),
+            None => report.feasible.push(game.id),
+        }
+        report.minimal_bag = report.minimal_bag.max(&game.max_set());
     }
+    report
 }
 
+pub fn parse_games(input: &str, palette: &mut Palette) -> Result<Vec<Game>, ParseError> {
+    input.lines().map(|line| Game::parse(line, palette)).collect()
+}
 
 #[aoc_generator(day2)]
-pub fn input_generator(input: &str) -> Vec<Game> {
-    input.lines().map(Game::from_str).collect()
+pub fn input_generator(input: &str) -> (Palette, Vec<Game>) {
+    let mut palette = Palette::open();
+    let games = parse_games(input, &mut palette).unwrap();
+    (palette, games)
 }
 
 #[aoc(day2, part1)]
-pub fn solve_part1(input: &[Game]) -> usize {
-    let max_set = CubeSet {
-        blue: 14,
-        green: 13,
-        red: 12,
-    };
+pub fn solve_part1((palette, games): &(Palette, Vec<Game>)) -> usize {
+    let bag = CubeSet::parse("12 red, 13 green, 14 blue", &mut palette.clone()).unwrap();
 
-    input.iter()
-        .filter(|game| game.cube_sets.iter().all(|set| set.smaller_than(&max_set)))
-        .map(|game| game.id)
-        .sum()
+    feasibility(games, &bag).feasible.iter().sum()
 }
 
 #[aoc(day2, part2)]
-pub fn solve_part2(input: &[Game]) -> usize {
-    input.iter()
-        .map(|game| game.max_set().power())
+pub fn solve_part2((palette, games): &(Palette, Vec<Game>)) -> usize {
+    games.iter()
+        .map(|game| game.max_set().power(palette))
         .sum()
 }
 
@@ -109,5 +284,40 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator(INPUT)), 2286);
     }
-}
 
+    #[test]
+    fn parse_arbitrary_colors() {
+        let (palette, games) = input_generator("\
+Game 1: 2 purple, 1 red; 3 purple
+Game 2: 1 red, 1 green, 1 blue");
+
+        assert_eq!(palette.colors(), ["purple", "red", "green", "blue"]);
+        assert_eq!(games[0].max_set().power(&palette), 0);
+        assert_eq!(games[1].max_set().power(&palette), 0);
+        assert_eq!(solve_part1(&(palette, games)), 2);
+
+        let mut rgb = Palette::closed(&["red", "green", "blue"]);
+        assert_eq!(Game::parse("Game 1: 2 purple", &mut rgb).unwrap_err(), ParseError::UnknownColor("purple".to_string()));
+        assert_eq!(Game::parse("Game x: 2 red", &mut rgb).unwrap_err(), ParseError::InvalidHeader("Game x".to_string()));
+        assert_eq!(CubeSet::parse("1 red, 0 blue", &mut rgb), CubeSet::parse("1 red", &mut rgb));
+    }
+
+    #[test]
+    fn feasibility_with_configured_bag() {
+        let (palette, games) = input_generator(INPUT);
+        let bag = CubeSet::parse_spec("red = 12\n# comment\n\ngreen=13\nblue=14".lines(), &palette).unwrap();
+        let report = feasibility(&games, &bag);
+
+        assert_eq!(report.feasible, [1, 2, 5]);
+        assert_eq!(report.infeasible.iter().map(|v| (v.game, v.draw)).collect::<Vec<_>>(), [(3, 0), (4, 2)]);
+        assert_eq!(report.infeasible[0].set.describe(&palette), "6 blue, 20 red, 8 green");
+        assert_eq!(report.minimal_bag.describe(&palette), "15 blue, 20 red, 13 green");
+        assert!(feasibility(&games, &report.minimal_bag).infeasible.is_empty());
+
+        let args = ["red=1", "green=oops"];
+        assert_eq!(CubeSet::parse_spec(args, &palette).unwrap_err(), ParseError::InvalidCount("oops".to_string()));
+        assert_eq!(CubeSet::parse_spec(["red"], &palette).unwrap_err(), ParseError::InvalidSpec("red".to_string()));
+        assert_eq!(CubeSet::parse_spec(["red=12", "gren=13", "blue=14"], &palette).unwrap_err(), ParseError::UnknownColor("gren".to_string()));
+        assert_eq!(palette.colors().len(), 3);
+    }
+}
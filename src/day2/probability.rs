@@ -0,0 +1,168 @@
+//! Likelihood of a game's draws under candidate bag compositions.
+//!
+//! Each draw is modelled as picking as many cubes as it shows, without replacement, from the full
+//! bag; cubes go back in the bag between draws. The probability of a draw is then the multivariate
+//! hypergeometric `Π C(bag_i, draw_i) / C(bag, draw)` and a game's likelihood the product over
+//! its draws.
+
+use num::integer::binomial;
+use num::{BigInt, BigRational, One, ToPrimitive, Zero};
+use super::{CubeSet, Game, Palette};
+
+fn total(set: &CubeSet, palette: &Palette) -> usize {
+    (0..palette.len()).map(|color| set.count(color)).sum()
+}
+
+pub fn draw_probability(draw: &CubeSet, bag: &CubeSet, palette: &Palette) -> BigRational {
+    if !draw.smaller_than(bag) {
+        return BigRational::zero();
+    }
+    let favorable = (0..palette.len())
+        .map(|color| binomial(BigInt::from(bag.count(color)), BigInt::from(draw.count(color))))
+        .product::<BigInt>();
+    let possible = binomial(BigInt::from(total(bag, palette)), BigInt::from(total(draw, palette)));
+    BigRational::new(favorable, possible)
+}
+
+pub fn likelihood(game: &Game, bag: &CubeSet, palette: &Palette) -> BigRational {
+    game.cube_sets()
+        .iter()
+        .map(|draw| draw_probability(draw, bag, palette))
+        .fold(BigRational::one(), |acc, p| acc * p)
+}
+
+/// Every bag holding exactly `total` cubes spread over the palette colours.
+pub fn bags_with_total(palette: &Palette, total: usize) -> Vec<CubeSet> {
+    fn fill(set: &mut CubeSet, color: usize, colors: usize, left: usize, out: &mut Vec<CubeSet>) {
+        if color == colors - 1 {
+            set.set(color, left);
+            out.push(set.clone());
+            return;
+        }
+        for count in 0..=left {
+            set.set(color, count);
+            fill(set, color + 1, colors, left - count, out);
+        }
+    }
+
+    let mut out = vec![];
+    if !palette.is_empty() {
+        fill(&mut CubeSet::default(), 0, palette.len(), total, &mut out);
+    }
+    out
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Estimate {
+    pub bag: CubeSet,
+    pub likelihood: BigRational,
+}
+
+/// Candidate with the highest likelihood, the first one winning ties. `None` if no candidate can
+/// produce the game.
+pub fn most_likely_bag<'a>(game: &Game, candidates: impl IntoIterator<Item=&'a CubeSet>, palette: &Palette) -> Option<Estimate> {
+    candidates.into_iter()
+        .map(|bag| Estimate { bag: bag.clone(), likelihood: likelihood(game, bag, palette) })
+        .filter(|estimate| !estimate.likelihood.is_zero())
+        .reduce(|best, estimate| if estimate.likelihood > best.likelihood { estimate } else { best })
+}
+
+/// Maximum-likelihood bag of every game, keyed by game id.
+pub fn most_likely_bags(games: &[Game], candidates: &[CubeSet], palette: &Palette) -> Vec<(usize, Option<Estimate>)> {
+    games.iter()
+        .map(|game| (game.id(), most_likely_bag(game, candidates, palette)))
+        .collect()
+}
+
+/// SplitMix64 generator, enough to drive the Monte-Carlo cross-check reproducibly.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+/// Monte-Carlo estimate of `likelihood`, drawing every set `samples` times from the bag.
+///
+/// Panics if `samples` is zero.
+pub fn simulate_likelihood(game: &Game, bag: &CubeSet, palette: &Palette, samples: usize, rng: &mut Rng) -> f64 {
+    assert!(samples > 0, "simulate_likelihood needs at least one sample");
+    let mut cubes = (0..palette.len())
+        .flat_map(|color| std::iter::repeat_n(color, bag.count(color)))
+        .collect::<Vec<_>>();
+
+    game.cube_sets().iter().map(|draw| {
+        let size = total(draw, palette);
+        if size > cubes.len() {
+            return 0.0;
+        }
+        let hits = (0..samples).filter(|_| {
+            let mut drawn = CubeSet::default();
+            for i in 0..size {
+                let j = i + rng.below(cubes.len() - i);
+                cubes.swap(i, j);
+                drawn.set(cubes[i], drawn.count(cubes[i]) + 1);
+            }
+            (0..palette.len()).all(|color| drawn.count(color) == draw.count(color))
+        }).count();
+        hits as f64 / samples as f64
+    }).product()
+}
+
+pub fn to_f64(probability: &BigRational) -> f64 {
+    probability.to_f64().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::input_generator;
+
+    #[test]
+    fn exact_likelihood_and_maximum() {
+        let (palette, games) = input_generator("\
+Game 1: 1 red; 1 blue
+Game 2: 2 red; 1 red, 1 blue");
+        let bag = CubeSet::parse("1 red, 1 blue", &mut palette.clone()).unwrap();
+        assert_eq!(likelihood(&games[0], &bag, &palette), BigRational::new(1.into(), 4.into()));
+        assert_eq!(likelihood(&games[1], &bag, &palette), BigRational::zero());
+
+        let candidates = bags_with_total(&palette, 4);
+        assert_eq!(candidates.len(), 5);
+        let estimates = most_likely_bags(&games, &candidates, &palette);
+        assert_eq!(estimates[0].1.as_ref().unwrap().bag.describe(&palette), "2 red, 2 blue");
+        assert_eq!(estimates[1].1.as_ref().unwrap().bag.describe(&palette), "3 red, 1 blue");
+        assert!(most_likely_bag(&games[1], &[bag], &palette).is_none());
+    }
+
+    #[test]
+    fn monte_carlo_agrees_with_exact() {
+        let (palette, games) = input_generator("Game 1: 1 red, 2 green; 2 blue, 1 red");
+        let bag = CubeSet::parse("3 red, 3 green, 3 blue", &mut palette.clone()).unwrap();
+        let exact = to_f64(&likelihood(&games[0], &bag, &palette));
+        let simulated = simulate_likelihood(&games[0], &bag, &palette, 20_000, &mut Rng::new(42));
+        assert!((exact - simulated).abs() < 0.01, "exact {exact}, simulated {simulated}");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one sample")]
+    fn monte_carlo_needs_samples() {
+        let (palette, games) = input_generator("Game 1: 1 red");
+        let bag = CubeSet::parse("1 red", &mut palette.clone()).unwrap();
+        simulate_likelihood(&games[0], &bag, &palette, 0, &mut Rng::new(42));
+    }
+}
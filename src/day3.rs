@@ -1,69 +1,462 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::ops::RangeInclusive;
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use regex::bytes::Regex;
-use atoi::atoi;
-use lazy_static::lazy_static;
+fn range(start: usize, end: usize, max: usize) -> impl Iterator<Item=usize> {
+    start.saturating_sub(1)..=(end).min(max - 1)
+}
 
-lazy_static! {
-    static ref NUMBER_PATTERN: Regex = Regex::new(r"\d+").unwrap();
+fn checked_digits_value(digits: &[char]) -> Option<u64> {
+    digits.iter().try_fold(0u64, |acc, d| acc.checked_mul(10)?.checked_add(d.to_digit(10).unwrap() as u64))
 }
 
-fn range(start: usize, end: usize, max: usize) -> impl Iterator<Item=usize> {
-    start.saturating_sub(1)..=(end).min(max - 1)
+/// Value of a digit run, which `Schema::parse` and `Schema::set` guarantee fits in a `u64`.
+fn digits_value(digits: &[char]) -> u64 {
+    checked_digits_value(digits).expect("number checked at parse time")
+}
+
+/// What to do with lines shorter or longer than the others.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Ragged {
+    #[default]
+    Reject,
+    /// Pad every line with `.` up to the longest one.
+    Pad,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SchemaError {
+    Empty,
+    Ragged {
+        line: usize,
+        width: usize,
+        expected: usize,
+    },
+    NumberOverflow {
+        line: usize,
+        column: usize,
+        digits: String,
+    },
+    /// Part number sum or gear total not fitting in a `u64`.
+    TotalOverflow,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Empty => write!(f, "empty schema"),
+            SchemaError::Ragged { line, width, expected } =>
+                write!(f, "line {line} is {width} cells wide, expected {expected}"),
+            SchemaError::NumberOverflow { line, column, digits } =>
+                write!(f, "line {line}, column {column}: number {digits} doesn't fit in 64 bits"),
+            SchemaError::TotalOverflow => write!(f, "part or gear total doesn't fit in 64 bits"),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Number {
-    line: usize,
-    head: usize,
-    tail: usize,
-    value: u64,
+impl Error for SchemaError {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Number {
+    pub line: usize,
+    pub head: usize,
+    pub tail: usize,
+    pub value: u64,
 }
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
-struct Neighbor {
-    x: usize,
-    y: usize,
-    byte: u8,
+pub struct Neighbor {
+    pub x: usize,
+    pub y: usize,
+    pub cell: char,
+}
+
+/// Inclusive bounds of a set of cells.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+/// Numbers and symbols forming an 8-connected group of non-`.` cells.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cluster {
+    pub numbers: Vec<Number>,
+    pub symbols: Vec<Neighbor>,
+    pub bounds: BoundingBox,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Combine {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Combine {
+    fn identity(self) -> u64 {
+        match self {
+            Combine::Product => 1,
+            Combine::Sum | Combine::Max => 0,
+        }
+    }
+
+    fn apply(self, acc: u64, value: u64) -> Option<u64> {
+        match self {
+            Combine::Product => acc.checked_mul(value),
+            Combine::Sum => acc.checked_add(value),
+            Combine::Max => Some(acc.max(value)),
+        }
+    }
+}
+
+/// Which symbols count as gears, how many adjacent numbers they need and how those combine.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GearRule {
+    pub symbols: Vec<char>,
+    pub arity: RangeInclusive<usize>,
+    pub combine: Combine,
+}
+
+impl GearRule {
+    /// Puzzle gears: a `*` adjacent to exactly two numbers, valued by their product.
+    pub fn ratio() -> Self {
+        Self {
+            symbols: vec!['*'],
+            arity: 2..=2,
+            combine: Combine::Product,
+        }
+    }
+}
+
+/// How a cell is highlighted when rendering the schema.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CellKind {
+    Empty,
+    Part,
+    NonPart,
+    Gear,
+    Symbol,
+}
+
+impl CellKind {
+    fn ansi(self) -> &'static str {
+        match self {
+            CellKind::Empty => "\x1b[2m",
+            CellKind::Part => "\x1b[32m",
+            CellKind::NonPart => "\x1b[31m",
+            CellKind::Gear => "\x1b[1;33m",
+            CellKind::Symbol => "\x1b[36m",
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            CellKind::Empty => "empty",
+            CellKind::Part => "part",
+            CellKind::NonPart => "non-part",
+            CellKind::Gear => "gear",
+            CellKind::Symbol => "symbol",
+        }
+    }
+}
+
+static HTML_STYLE: &str = "\
+.schema .empty { color: #999; }
+.schema .part { color: #2a2; }
+.schema .non-part { color: #c22; }
+.schema .gear { color: #d90; font-weight: bold; }
+.schema .symbol { color: #29c; }";
+
+/// Part number sum and gear total, kept up to date across `Schema::set`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Totals {
+    pub parts: u64,
+    pub gears: u64,
 }
 
 #[derive(Debug)]
 pub struct Schema {
     height: usize,
     width: usize,
-    grid: Vec<Vec<u8>>,
+    grid: Vec<Vec<char>>,
+    gear_rule: GearRule,
+    totals: Totals,
 }
 
 impl Schema {
-    fn new(grid: Vec<Vec<u8>>) -> Self {
-        Self {
+    /// Parses one cell per `char`, so multi-byte symbols take a single cell. Ragged lines are
+    /// reported against the most common width.
+    pub fn parse(input: &str, ragged: Ragged) -> Result<Self, SchemaError> {
+        let mut grid = input.lines().map(|line| line.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        if grid.is_empty() {
+            return Err(SchemaError::Empty);
+        }
+        match ragged {
+            Ragged::Reject => {
+                // The most common width, the earliest one on ties, so the odd line is blamed.
+                let mut counts = HashMap::<usize, usize>::new();
+                grid.iter().for_each(|line| *counts.entry(line.len()).or_default() += 1);
+                let expected = grid.iter()
+                    .map(Vec::len)
+                    .rev()
+                    .max_by_key(|width| counts[width])
+                    .unwrap();
+                if let Some(y) = grid.iter().position(|line| line.len() != expected) {
+                    return Err(SchemaError::Ragged { line: y + 1, width: grid[y].len(), expected });
+                }
+            }
+            Ragged::Pad => {
+                let width = grid.iter().map(Vec::len).max().unwrap();
+                grid.iter_mut().for_each(|line| line.resize(width, '.'));
+            }
+        }
+        if grid[0].is_empty() {
+            return Err(SchemaError::Empty);
+        }
+        for (y, line) in grid.iter().enumerate() {
+            let mut head = 0;
+            while let Some(offset) = line[head..].iter().position(|c| c.is_ascii_digit()) {
+                head += offset;
+                let tail = line[head..].iter().position(|c| !c.is_ascii_digit()).map_or(line.len(), |i| head + i);
+                if checked_digits_value(&line[head..tail]).is_none() {
+                    return Err(Self::overflow(y, head, &line[head..tail]));
+                }
+                head = tail;
+            }
+        }
+        Self::new(grid)
+    }
+
+    fn overflow(y: usize, head: usize, digits: &[char]) -> SchemaError {
+        SchemaError::NumberOverflow { line: y + 1, column: head + 1, digits: digits.iter().collect() }
+    }
+
+    fn new(grid: Vec<Vec<char>>) -> Result<Self, SchemaError> {
+        let mut schema = Self {
             height: grid.len(),
             width: grid[0].len(),
             grid,
+            gear_rule: GearRule::ratio(),
+            totals: Totals::default(),
+        };
+        schema.totals = schema.compute_totals()?;
+        Ok(schema)
+    }
+
+    /// Changes the rule the live gear total is maintained for.
+    pub fn with_gear_rule(mut self, rule: GearRule) -> Result<Self, SchemaError> {
+        self.gear_rule = rule;
+        self.totals = self.compute_totals()?;
+        Ok(self)
+    }
+
+    pub fn totals(&self) -> Totals {
+        self.totals
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> char {
+        self.grid[y][x]
+    }
+
+    /// Overwrites a cell, updating the totals from the numbers and gears around it only. The
+    /// edit is refused if it makes a number or a total too large.
+    pub fn set(&mut self, x: usize, y: usize, cell: char) -> Result<(), SchemaError> {
+        let previous = self.grid[y][x];
+        if previous == cell {
+            return Ok(());
+        }
+
+        let before = self.numbers_around(x, y);
+        self.grid[y][x] = cell;
+        if cell.is_ascii_digit() {
+            let line = &self.grid[y];
+            let head = line[..x].iter().rposition(|c| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+            let tail = line[x..].iter().position(|c| !c.is_ascii_digit()).map_or(line.len(), |i| x + i);
+            if checked_digits_value(&line[head..tail]).is_none() {
+                let error = Self::overflow(y, head, &line[head..tail]);
+                self.grid[y][x] = previous;
+                return Err(error);
+            }
+        }
+        let after = self.numbers_around(x, y);
+
+        // Changed numbers lie in the 3x3 block around the cell, but may stretch along the row.
+        let (mut min_x, mut max_x) = (x, x);
+        for number in before.iter().chain(after.iter()) {
+            min_x = min_x.min(number.head);
+            max_x = max_x.max(number.tail - 1);
+        }
+        let region = BoundingBox {
+            min_x: min_x.saturating_sub(1),
+            min_y: y.saturating_sub(2),
+            max_x: (max_x + 1).min(self.width - 1),
+            max_y: (y + 2).min(self.height - 1),
+        };
+
+        self.grid[y][x] = previous;
+        let old = self.local_totals(&before, &region).expect("within the current totals");
+        self.grid[y][x] = cell;
+        let totals = self.local_totals(&after, &region).and_then(|new| Some(Totals {
+            parts: (self.totals.parts - old.parts).checked_add(new.parts)?,
+            gears: (self.totals.gears - old.gears).checked_add(new.gears)?,
+        }));
+        match totals {
+            Some(totals) => {
+                self.totals = totals;
+                Ok(())
+            }
+            None => {
+                self.grid[y][x] = previous;
+                Err(SchemaError::TotalOverflow)
+            }
+        }
+    }
+
+    /// Classifies every cell: numbers by whether they are part numbers, symbols by whether they
+    /// are gears under the schema's gear rule.
+    pub fn cell_kinds(&self) -> Vec<Vec<CellKind>> {
+        let mut kinds = self.grid.iter()
+            .map(|line| line.iter().map(|c| if *c == '.' { CellKind::Empty } else { CellKind::Symbol }).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for number in self.numbers() {
+            let kind = if self.is_adjacent_to_symbol(&number) { CellKind::Part } else { CellKind::NonPart };
+            kinds[number.line][number.head..number.tail].fill(kind);
+        }
+        for (gear, _) in self.gears(&self.gear_rule).expect("checked by the totals") {
+            kinds[gear.y][gear.x] = CellKind::Gear;
+        }
+        kinds
+    }
+
+    /// Runs of consecutive cells of the same kind, line by line.
+    fn runs(&self) -> impl Iterator<Item=Vec<(CellKind, &[char])>> + '_ {
+        self.cell_kinds().into_iter().zip(self.grid.iter()).map(|(kinds, line)| {
+            let mut runs = vec![];
+            let mut head = 0;
+            for tail in 1..=line.len() {
+                if tail == line.len() || kinds[tail] != kinds[head] {
+                    runs.push((kinds[head], &line[head..tail]));
+                    head = tail;
+                }
+            }
+            runs
+        })
+    }
+
+    pub fn render_ansi(&self) -> String {
+        self.runs()
+            .map(|runs| runs.iter()
+                .map(|(kind, cells)| format!("{}{}\x1b[0m", kind.ansi(), cells.iter().collect::<String>()))
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn render_html(&self) -> String {
+        let lines = self.runs()
+            .map(|runs| runs.iter()
+                .map(|(kind, cells)| {
+                    let text = cells.iter().collect::<String>()
+                        .replace('&', "&amp;")
+                        .replace('<', "&lt;")
+                        .replace('>', "&gt;");
+                    format!("<span class=\"{}\">{}</span>", kind.class(), text)
+                })
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("<style>\n{HTML_STYLE}\n</style>\n<pre class=\"schema\">\n{lines}\n</pre>\n")
+    }
+
+    fn parts_total(&self, numbers: &[Number]) -> Option<u64> {
+        numbers.iter()
+            .filter(|n| self.is_adjacent_to_symbol(n))
+            .try_fold(0u64, |acc, n| acc.checked_add(n.value))
+    }
+
+    fn compute_totals(&self) -> Result<Totals, SchemaError> {
+        Ok(Totals {
+            parts: self.parts_total(&self.numbers().collect::<Vec<_>>()).ok_or(SchemaError::TotalOverflow)?,
+            gears: self.gear_total(&self.gear_rule)?,
+        })
+    }
+
+    /// `None` when a gear or a total of the region overflows.
+    fn local_totals(&self, numbers: &[Number], region: &BoundingBox) -> Option<Totals> {
+        let mut gears = 0u64;
+        for y in region.min_y..=region.max_y {
+            for x in region.min_x..=region.max_x {
+                if let Some(value) = self.gear_value(x, y, &self.gear_rule)? {
+                    gears = gears.checked_add(value)?;
+                }
+            }
+        }
+        Some(Totals { parts: self.parts_total(numbers)?, gears })
+    }
+
+    fn number_at(&self, x: usize, y: usize) -> Option<Number> {
+        let line = &self.grid[y];
+        if !line[x].is_ascii_digit() {
+            return None;
+        }
+        let head = line[..x].iter().rposition(|c| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+        let tail = line[x..].iter().position(|c| !c.is_ascii_digit()).map_or(line.len(), |i| x + i);
+        Some(Number { line: y, head, tail, value: digits_value(&line[head..tail]) })
+    }
+
+    /// Distinct numbers covering at least one cell of the 3x3 block centred on `(x, y)`.
+    fn numbers_around(&self, x: usize, y: usize) -> Vec<Number> {
+        let mut numbers = Vec::<Number>::new();
+        for ny in range(y, y + 1, self.height) {
+            for nx in range(x, x + 1, self.width) {
+                if let Some(number) = self.number_at(nx, ny) {
+                    if !numbers.contains(&number) {
+                        numbers.push(number);
+                    }
+                }
+            }
+        }
+        numbers
+    }
+
+    /// Value of the gear at `(x, y)` if there is one, `None` if that value overflows.
+    fn gear_value(&self, x: usize, y: usize, rule: &GearRule) -> Option<Option<u64>> {
+        if !rule.symbols.contains(&self.grid[y][x]) {
+            return Some(None);
+        }
+        let numbers = self.numbers_around(x, y);
+        if !rule.arity.contains(&numbers.len()) {
+            return Some(None);
         }
+        numbers.iter().try_fold(rule.combine.identity(), |acc, n| rule.combine.apply(acc, n.value)).map(Some)
     }
 
-    fn numbers(&self) -> impl Iterator<Item=Number> + '_ {
+    pub fn numbers(&self) -> impl Iterator<Item=Number> + '_ {
         self.grid
             .iter()
             .enumerate()
-            .flat_map(|(y, line)|
-                NUMBER_PATTERN.find_iter(line)
-                    .map(move |m| Number {
-                        line: y,
-                        head: m.start(),
-                        tail: m.end(),
-                        value: atoi(m.as_bytes()).unwrap(),
-                    })
-            )
+            .flat_map(|(y, line)| {
+                let mut head = 0;
+                std::iter::from_fn(move || {
+                    head += line[head..].iter().position(|c| c.is_ascii_digit())?;
+                    let tail = line[head..].iter().position(|c| !c.is_ascii_digit()).map_or(line.len(), |i| head + i);
+                    let number = Number { line: y, head, tail, value: digits_value(&line[head..tail]) };
+                    head = tail;
+                    Some(number)
+                })
+            })
     }
 
-    fn neighbors<'a>(&'a self, number: &'a Number) -> impl Iterator<Item=Neighbor> + 'a {
+    pub fn neighbors<'a>(&'a self, number: &'a Number) -> impl Iterator<Item=Neighbor> + 'a {
         range(number.line, number.line + 1, self.height).flat_map(move |y|
             range(number.head, number.tail, self.width).filter_map(move |x|
                 if y != number.line || (x < number.head || number.tail <= x) {
-                    Some(Neighbor { x, y, byte: self.grid[y][x] })
+                    Some(Neighbor { x, y, cell: self.grid[y][x] })
                 } else {
                     None
                 }
@@ -72,17 +465,91 @@ impl Schema {
     }
 
     fn is_adjacent_to_symbol(&self, number: &Number) -> bool {
-        self.neighbors(number).any(|n| n.byte != b'.' && !n.byte.is_ascii_digit())
+        self.neighbors(number).any(|n| n.cell != '.' && !n.cell.is_ascii_digit())
+    }
+
+    fn adjacent_gears<'a>(&'a self, number: &'a Number, rule: &'a GearRule) -> impl Iterator<Item=Neighbor> + 'a {
+        self.neighbors(number).filter(|n| rule.symbols.contains(&n.cell))
+    }
+
+    /// Gears satisfying `rule` with their combined value, in reading order.
+    pub fn gears(&self, rule: &GearRule) -> Result<Vec<(Neighbor, u64)>, SchemaError> {
+        // Values are `None` once they overflow, which only matters for gears passing the rule.
+        let mut gears = HashMap::<Neighbor, (usize, Option<u64>)>::new();
+        for number in self.numbers() {
+            for gear in self.adjacent_gears(&number, rule) {
+                let (count, value) = gears.entry(gear).or_insert((0, Some(rule.combine.identity())));
+                *count += 1;
+                *value = value.and_then(|value| rule.combine.apply(value, number.value));
+            }
+        }
+        let mut gears = gears.into_iter()
+            .filter(|(_, (count, _))| rule.arity.contains(count))
+            .map(|(gear, (_, value))| value.map(|value| (gear, value)).ok_or(SchemaError::TotalOverflow))
+            .collect::<Result<Vec<_>, _>>()?;
+        gears.sort_by_key(|(gear, _)| (gear.y, gear.x));
+        Ok(gears)
     }
 
-    fn adjacent_gears<'a>(&'a self, number: &'a Number) -> impl Iterator<Item=Neighbor> + '_ {
-        self.neighbors(number).filter(|n| n.byte == b'*')
+    pub fn gear_total(&self, rule: &GearRule) -> Result<u64, SchemaError> {
+        self.gears(rule)?.iter()
+            .try_fold(0u64, |acc, (_, value)| acc.checked_add(*value))
+            .ok_or(SchemaError::TotalOverflow)
+    }
+
+    /// Groups every non-`.` cell into 8-connected clusters, in reading order of their first cell.
+    pub fn clusters(&self) -> Vec<Cluster> {
+        let mut labels = vec![vec![None; self.width]; self.height];
+        let mut clusters = vec![];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.grid[y][x] == '.' || labels[y][x].is_some() {
+                    continue;
+                }
+                let label = clusters.len();
+                let mut cluster = Cluster {
+                    numbers: vec![],
+                    symbols: vec![],
+                    bounds: BoundingBox { min_x: x, min_y: y, max_x: x, max_y: y },
+                };
+                let mut queue = VecDeque::from([(x, y)]);
+                labels[y][x] = Some(label);
+                while let Some((x, y)) = queue.pop_front() {
+                    let bounds = &mut cluster.bounds;
+                    bounds.min_x = bounds.min_x.min(x);
+                    bounds.min_y = bounds.min_y.min(y);
+                    bounds.max_x = bounds.max_x.max(x);
+                    bounds.max_y = bounds.max_y.max(y);
+                    if !self.grid[y][x].is_ascii_digit() {
+                        cluster.symbols.push(Neighbor { x, y, cell: self.grid[y][x] });
+                    }
+                    for ny in range(y, y + 1, self.height) {
+                        for nx in range(x, x + 1, self.width) {
+                            if self.grid[ny][nx] != '.' && labels[ny][nx].is_none() {
+                                labels[ny][nx] = Some(label);
+                                queue.push_back((nx, ny));
+                            }
+                        }
+                    }
+                }
+                cluster.symbols.sort_by_key(|s| (s.y, s.x));
+                clusters.push(cluster);
+            }
+        }
+
+        for number in self.numbers() {
+            let label = labels[number.line][number.head].unwrap();
+            clusters[label].numbers.push(number);
+        }
+
+        clusters
     }
 }
 
 #[aoc_generator(day3)]
 pub fn input_generator(input: &str) -> Schema {
-    Schema::new(input.lines().map(|s| s.as_bytes().to_vec()).collect())
+    Schema::parse(input, Ragged::Reject).unwrap()
 }
 
 #[aoc(day3, part1)]
@@ -94,20 +561,7 @@ pub fn solve_part1(input: &Schema) -> u64 {
 
 #[aoc(day3, part2)]
 pub fn solve_part2(input: &Schema) -> u64 {
-    let mut gears = HashMap::<Neighbor, (usize, u64)>::new();
-    for number in input.numbers() {
-        for gear in input.adjacent_gears(&number) {
-            gears.entry(gear)
-                .and_modify(|(count, power)| {
-                    *count += 1;
-                    *power *= number.value;
-                })
-                .or_insert((1, number.value));
-        }
-    }
-    gears.values()
-        .filter_map(|(count, power)| if *count == 2 { Some(power) } else { None })
-        .sum()
+    input.gear_total(&GearRule::ratio()).unwrap()
 }
 
 #[cfg(test)]
@@ -135,4 +589,122 @@ mod tests {
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator(INPUT)), 467835);
     }
+
+    #[test]
+    fn clusters_match_example() {
+        let clusters = input_generator(INPUT).clusters();
+        assert_eq!(clusters.len(), 8);
+
+        let values = clusters.iter()
+            .map(|c| c.numbers.iter().map(|n| n.value).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [vec![467, 35], vec![114], vec![633], vec![617], vec![592], vec![58], vec![755, 598], vec![664]]);
+
+        assert_eq!(clusters[0].symbols, [Neighbor { x: 3, y: 1, cell: '*' }]);
+        assert_eq!(clusters[0].bounds, BoundingBox { min_x: 0, min_y: 0, max_x: 3, max_y: 2 });
+        assert!(clusters[1].symbols.is_empty());
+        assert_eq!(clusters[6].bounds, BoundingBox { min_x: 5, min_y: 7, max_x: 8, max_y: 9 });
+    }
+
+    #[test]
+    fn gear_rules() {
+        let schema = input_generator(INPUT);
+        assert_eq!(schema.gears(&GearRule::ratio()).unwrap().len(), 2);
+
+        let any_star = GearRule { symbols: vec!['*'], arity: 1..=usize::MAX, combine: Combine::Sum };
+        assert_eq!(schema.gear_total(&any_star).unwrap(), 467 + 35 + 617 + 755 + 598);
+
+        let lonely = GearRule { symbols: vec!['*', '#', '$'], arity: 1..=1, combine: Combine::Max };
+        assert_eq!(schema.gears(&lonely).unwrap(), [
+            (Neighbor { x: 6, y: 3, cell: '#' }, 633),
+            (Neighbor { x: 3, y: 4, cell: '*' }, 617),
+            (Neighbor { x: 3, y: 8, cell: '$' }, 664),
+        ]);
+    }
+
+    #[test]
+    fn edits_keep_totals_in_sync() {
+        let mut schema = input_generator(INPUT);
+        assert_eq!(schema.totals(), Totals { parts: 4361, gears: 467835 });
+
+        schema.set(3, 4, '.').unwrap();
+        assert_eq!(schema.totals(), Totals { parts: 4361 - 617, gears: 467835 });
+        schema.set(2, 1, '7').unwrap();
+        assert_eq!(schema.totals(), Totals { parts: 4361 - 617 + 7, gears: 467835 - 467 * 35 });
+
+        let cells = ['.', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '*', '#', '$', '+'];
+        let mut seed = 7u64;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let r = (seed >> 33) as usize;
+            schema.set(r % 10, (r / 10) % 10, cells[(r / 100) % cells.len()]).unwrap();
+            assert_eq!(schema.totals(), Totals { parts: solve_part1(&schema), gears: solve_part2(&schema) });
+        }
+    }
+
+    #[test]
+    fn render_highlights() {
+        let schema = input_generator("467..114..\n...*......\n..35..6<3.");
+
+        assert_eq!(schema.cell_kinds()[0][..6], [CellKind::Part, CellKind::Part, CellKind::Part, CellKind::Empty, CellKind::Empty, CellKind::NonPart]);
+        assert_eq!(schema.cell_kinds()[1][3], CellKind::Gear);
+
+        let ansi = schema.render_ansi();
+        assert!(ansi.starts_with("\x1b[32m467\x1b[0m\x1b[2m..\x1b[0m\x1b[31m114\x1b[0m"));
+        assert!(ansi.contains("\x1b[1;33m*\x1b[0m"));
+
+        let html = schema.render_html();
+        assert!(html.contains("<span class=\"part\">467</span><span class=\"empty\">..</span><span class=\"non-part\">114</span>"));
+        assert!(html.contains("<span class=\"part\">6</span><span class=\"symbol\">&lt;</span><span class=\"part\">3</span>"));
+    }
+
+    #[test]
+    fn parse_ragged_and_unicode() {
+        assert_eq!(
+            Schema::parse("467..\n...*\n..35.", Ragged::Reject).unwrap_err(),
+            SchemaError::Ragged { line: 2, width: 4, expected: 5 },
+        );
+        assert_eq!(
+            Schema::parse("12\n345\n678", Ragged::Reject).unwrap_err(),
+            SchemaError::Ragged { line: 1, width: 2, expected: 3 },
+        );
+        assert_eq!(Schema::parse("", Ragged::Pad).unwrap_err(), SchemaError::Empty);
+
+        let schema = Schema::parse("467..\n...★\n..35", Ragged::Pad).unwrap();
+        assert_eq!(schema.get(4, 2), '.');
+        assert_eq!(solve_part1(&schema), 467 + 35);
+
+        let star = GearRule { symbols: vec!['★'], ..GearRule::ratio() };
+        assert_eq!(schema.gears(&star).unwrap(), [(Neighbor { x: 3, y: 1, cell: '★' }, 467 * 35)]);
+        assert_eq!(schema.clusters().len(), 1);
+    }
+
+    #[test]
+    fn reject_numbers_overflowing_u64() {
+        assert_eq!(
+            Schema::parse("..*\n.1234567890123456789012345", Ragged::Pad).unwrap_err(),
+            SchemaError::NumberOverflow { line: 2, column: 2, digits: "1234567890123456789012345".to_string() },
+        );
+
+        let mut schema = Schema::parse("1844674407370955161.", Ragged::Reject).unwrap();
+        assert_eq!(schema.set(19, 0, '5'), Ok(()));
+        assert_eq!(schema.totals().parts, 0);
+        schema.set(19, 0, '.').unwrap();
+        assert!(matches!(schema.set(19, 0, '6'), Err(SchemaError::NumberOverflow { line: 1, column: 1, .. })));
+        assert_eq!(schema.get(19, 0), '.');
+    }
+
+    #[test]
+    fn reject_totals_overflowing_u64() {
+        assert_eq!(Schema::parse("4294967296*4294967296", Ragged::Reject).unwrap_err(), SchemaError::TotalOverflow);
+
+        let mut schema = Schema::parse("4294967296*.294967296", Ragged::Reject).unwrap();
+        assert_eq!(schema.set(11, 0, '4'), Err(SchemaError::TotalOverflow));
+        assert_eq!(schema.get(11, 0), '.');
+        assert_eq!(schema.totals(), Totals { parts: 4294967296, gears: 0 });
+
+        let hash = GearRule { symbols: vec!['#'], ..GearRule::ratio() };
+        let schema = Schema::parse("4294967296#4294967296", Ragged::Reject).unwrap();
+        assert_eq!(schema.with_gear_rule(hash).unwrap_err(), SchemaError::TotalOverflow);
+    }
 }
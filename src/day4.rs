@@ -1,63 +1,294 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::{CheckedAdd, One, Zero};
 
-fn parse_num_line(line: &str) -> HashSet<usize> {
-    line.split_whitespace().map(|raw| raw.parse().unwrap()).collect()
+/// Numbers of a card side, one bit per number.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct NumberSet(u128);
+
+impl NumberSet {
+    pub const CAPACITY: usize = 128;
+
+    /// Adds `n`, returning `false` if it doesn't fit in the set.
+    pub fn insert(&mut self, n: usize) -> bool {
+        if n >= Self::CAPACITY {
+            return false;
+        }
+        self.0 |= 1 << n;
+        true
+    }
+
+    pub fn contains(&self, n: usize) -> bool {
+        n < Self::CAPACITY && self.0 & (1 << n) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self(self.0 & other.0)
+    }
 }
 
+impl FromStr for NumberSet {
+    type Err = ParseCardError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut set = NumberSet::default();
+        for raw in line.split_whitespace() {
+            let n = raw.parse::<usize>().map_err(|_| ParseCardError::InvalidNumber(raw.to_string()))?;
+            if !set.insert(n) {
+                return Err(ParseCardError::OutOfRange(n));
+            }
+        }
+        Ok(set)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseCardError {
+    MissingSeparator(String),
+    InvalidHeader(String),
+    InvalidNumber(String),
+    OutOfRange(usize),
+}
+
+impl Display for ParseCardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCardError::MissingSeparator(line) => write!(f, "expected `Card <id>: <numbers> | <numbers>`, got `{line}`"),
+            ParseCardError::InvalidHeader(header) => write!(f, "expected `Card <id>`, got `{header}`"),
+            ParseCardError::InvalidNumber(raw) => write!(f, "invalid number `{raw}`"),
+            ParseCardError::OutOfRange(n) => write!(f, "number {n} doesn't fit in a card (max {})", NumberSet::CAPACITY - 1),
+        }
+    }
+}
+
+impl Error for ParseCardError {}
+
+/// Scratchcard whose matching count is computed once, at parse time.
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Card {
-    winning_numbers: HashSet<usize>,
-    scratched_numbers: HashSet<usize>,
+    id: usize,
+    winning_numbers: NumberSet,
+    scratched_numbers: NumberSet,
+    wins: usize,
+}
+
+impl FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (header, winnings, scratched) = line.split_once(": ")
+            .and_then(|(header, numbers)| numbers.split_once(" | ").map(|(w, s)| (header, w, s)))
+            .ok_or_else(|| ParseCardError::MissingSeparator(line.to_string()))?;
+        let id = header.strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .ok_or_else(|| ParseCardError::InvalidHeader(header.to_string()))?;
+        let winning_numbers = winnings.parse::<NumberSet>()?;
+        let scratched_numbers = scratched.parse::<NumberSet>()?;
+        Ok(Self {
+            id,
+            wins: winning_numbers.intersection(&scratched_numbers).len(),
+            winning_numbers,
+            scratched_numbers,
+        })
+    }
 }
 
 impl Card {
-    pub fn from_str(line: &str) -> Self {
-        let (winnings, scratched) = line.split(": ").nth(1).unwrap().split_once(" | ").unwrap();
-        Self {
-            winning_numbers: parse_num_line(winnings),
-            scratched_numbers: parse_num_line(scratched),
-        }
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn winning_numbers(&self) -> NumberSet {
+        self.winning_numbers
+    }
+
+    pub fn scratched_numbers(&self) -> NumberSet {
+        self.scratched_numbers
     }
 
-    pub fn points(&self) -> usize {
-        let n = self.winning_numbers.intersection(&self.scratched_numbers).count() as u32;
-        if n == 0 {
+    /// Card worth, as `u128` since a card can match up to `NumberSet::CAPACITY` numbers.
+    pub fn points(&self) -> u128 {
+        if self.wins == 0 {
             0
         } else {
-            2usize.pow(n - 1)
+            1 << (self.wins - 1)
         }
     }
 
     pub fn wins(&self) -> usize {
-        self.winning_numbers.intersection(&self.scratched_numbers).count()
+        self.wins
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CardIssue {
+    Duplicate(usize),
+    /// Id between the smallest and largest ones that no card carries.
+    Missing(usize),
+    OutOfOrder {
+        card: usize,
+        previous: usize,
+    },
+}
+
+impl Display for CardIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CardIssue::Duplicate(id) => write!(f, "card {id} appears more than once"),
+            CardIssue::Missing(id) => write!(f, "card {id} is missing"),
+            CardIssue::OutOfOrder { card, previous } => write!(f, "card {card} comes after card {previous}"),
+        }
+    }
+}
+
+/// Checks that card ids are unique, contiguous and in increasing order.
+pub fn validate(cards: &[Card]) -> Vec<CardIssue> {
+    let mut issues = vec![];
+    let mut seen = HashSet::new();
+    for (i, card) in cards.iter().enumerate() {
+        if !seen.insert(card.id) {
+            issues.push(CardIssue::Duplicate(card.id));
+        }
+        if i > 0 && card.id < cards[i - 1].id {
+            issues.push(CardIssue::OutOfOrder { card: card.id, previous: cards[i - 1].id });
+        }
+    }
+    if let (Some(min), Some(max)) = (seen.iter().min(), seen.iter().max()) {
+        issues.extend((*min..*max).filter(|id| !seen.contains(id)).map(CardIssue::Missing));
+    }
+    issues
+}
+
+/// What to do with a card winning copies of cards that don't exist, past the last one or
+/// missing from a sparse list.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum OutOfRange {
+    /// Only the existing cards are copied.
+    #[default]
+    Clip,
+    Error,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CascadeError {
+    OutOfRange {
+        card: usize,
+        missing: usize,
+    },
+    /// Card winning copies past `usize::MAX`, rejected under `OutOfRange::Error`.
+    PastLastId {
+        card: usize,
+    },
+    Duplicate {
+        card: usize,
+    },
+    Overflow {
+        card: usize,
+    },
+}
+
+impl Display for CascadeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CascadeError::OutOfRange { card, missing } =>
+                write!(f, "card {card} wins a copy of card {missing} which doesn't exist"),
+            CascadeError::PastLastId { card } => write!(f, "card {card} wins copies past the largest card id"),
+            CascadeError::Duplicate { card } => write!(f, "card {card} appears more than once"),
+            CascadeError::Overflow { card } => write!(f, "copy count of card {card} overflows"),
+        }
+    }
+}
+
+impl Error for CascadeError {}
+
+/// Number of copies held of every card, keyed by card id.
+///
+/// Counts are generic so `usize` gives checked arithmetic and `BigUint` never overflows.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cascade<C> {
+    pub copies: BTreeMap<usize, C>,
+}
+
+impl<C: Clone + One + Zero + CheckedAdd> Cascade<C> {
+    /// Card `n` winning `w` copies wins one of each of cards `n + 1` to `n + w`, whatever the
+    /// order of the list.
+    pub fn new(cards: &[Card], policy: OutOfRange) -> Result<Self, CascadeError> {
+        let mut wins = BTreeMap::new();
+        for card in cards {
+            if wins.insert(card.id, card.wins).is_some() {
+                return Err(CascadeError::Duplicate { card: card.id });
+            }
+        }
+
+        let mut copies = wins.keys().map(|id| (*id, C::one())).collect::<BTreeMap<_, _>>();
+        for (&id, &win) in &wins {
+            let count = copies[&id].clone();
+            if policy == OutOfRange::Error && id.checked_add(win).is_none() {
+                return Err(CascadeError::PastLastId { card: id });
+            }
+            for won in (1..=win).map_while(|k| id.checked_add(k)) {
+                match copies.get_mut(&won) {
+                    Some(copies) => *copies = copies.checked_add(&count)
+                        .ok_or(CascadeError::Overflow { card: won })?,
+                    None if policy == OutOfRange::Error =>
+                        return Err(CascadeError::OutOfRange { card: id, missing: won }),
+                    None => {}
+                }
+            }
+        }
+        Ok(Self { copies })
+    }
+
+    pub fn total(&self) -> Result<C, CascadeError> {
+        self.copies.iter().try_fold(C::zero(), |acc, (id, copies)| {
+            acc.checked_add(copies).ok_or(CascadeError::Overflow { card: *id })
+        })
+    }
+}
+
+impl<C: Display> Display for Cascade<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (id, copies) in &self.copies {
+            writeln!(f, "card {id}: {copies}")?;
+        }
+        Ok(())
     }
 }
 
 #[aoc_generator(day4)]
 pub fn input_generator(input: &str) -> Vec<Card> {
-    input.lines().map(Card::from_str).collect()
+    input.lines().map(|line| line.parse().unwrap()).collect()
+}
+
+/// Sum of the card points, `None` if it doesn't fit in a `u128`.
+pub fn total_points(cards: &[Card]) -> Option<u128> {
+    cards.iter().try_fold(0u128, |acc, card| acc.checked_add(card.points()))
 }
 
 #[aoc(day4, part1)]
-pub fn solve_part1(input: &[Card]) -> usize {
-    input.iter().map(Card::points).sum()
+pub fn solve_part1(input: &[Card]) -> u128 {
+    total_points(input).expect("total points overflow u128")
 }
 
 #[aoc(day4, part2)]
 pub fn solve_part2(input: &[Card]) -> usize {
-    let wins = input.iter().map(Card::wins).collect::<Vec<_>>();
-    let mut counts = vec![1; input.len()];
-    for (i, win) in wins.iter().enumerate() {
-        for j in i+1..i+win+1 {
-            counts[j] += counts[i];
-        }
-    }
-    counts.iter().sum()
+    Cascade::<usize>::new(input, OutOfRange::Clip).unwrap().total().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{input_generator, solve_part1, solve_part2};
+    use super::*;
 
     static INPUT: &str = "\
 Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
@@ -76,5 +307,79 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator(INPUT)), 30);
     }
-}
 
+    #[test]
+    fn parse_card_bitsets() {
+        let card = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53".parse::<Card>().unwrap();
+        assert_eq!(card.wins(), 4);
+        assert_eq!(card.winning_numbers().len(), 5);
+        assert!(card.scratched_numbers().contains(6));
+        assert!(!card.scratched_numbers().contains(200));
+
+        assert_eq!("Card 1: 1 200 | 1".parse::<Card>().unwrap_err(), ParseCardError::OutOfRange(200));
+        assert_eq!("Card 1: 1 x | 1".parse::<Card>().unwrap_err(), ParseCardError::InvalidNumber("x".to_string()));
+
+        let numbers = (0..128).map(|n| n.to_string()).collect::<Vec<_>>().join(" ");
+        let full = format!("Card 1: {numbers} | {numbers}").parse::<Card>().unwrap();
+        assert_eq!(full.points(), 1 << 127);
+        assert_eq!(total_points(&[full.clone(), full.clone()]), None);
+        assert_eq!(total_points(&[full.clone(), card]), Some((1 << 127) + 8));
+        let mut set = NumberSet::default();
+        assert!(!set.insert(128));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn cascade_policies() {
+        let cards = input_generator("\
+Card 1: 1 2 3 | 1 2 3
+Card 2: 1 2 | 1 2
+Card 3: 1 | 1");
+
+        assert_eq!(Cascade::<usize>::new(&cards, OutOfRange::Clip).unwrap().copies.into_values().collect::<Vec<_>>(), [1, 2, 4]);
+        assert_eq!(
+            Cascade::<usize>::new(&cards, OutOfRange::Error).unwrap_err(),
+            CascadeError::OutOfRange { card: 1, missing: 4 },
+        );
+
+        let cascade = Cascade::<num::BigUint>::new(&input_generator(INPUT), OutOfRange::Error).unwrap();
+        assert_eq!(cascade.total().unwrap(), num::BigUint::from(30u32));
+        assert!(cascade.to_string().starts_with("card 1: 1\ncard 2: 2\ncard 3: 4\n"));
+    }
+
+    #[test]
+    fn cascade_detects_overflow() {
+        let chain = (1..=70).map(|id| format!("Card {id}: 1 2 | 1 2")).collect::<Vec<_>>().join("\n");
+        let cards = input_generator(&chain);
+        assert_eq!(Cascade::<u32>::new(&cards, OutOfRange::Clip).unwrap_err(), CascadeError::Overflow { card: 46 });
+        assert!(Cascade::<num::BigUint>::new(&cards, OutOfRange::Clip).is_ok());
+
+        let last = input_generator(&format!("Card {}: 1 | 1", usize::MAX));
+        assert_eq!(Cascade::<usize>::new(&last, OutOfRange::Clip).unwrap().total(), Ok(1));
+        assert_eq!(Cascade::<usize>::new(&last, OutOfRange::Error).unwrap_err(), CascadeError::PastLastId { card: usize::MAX });
+    }
+
+    #[test]
+    fn cards_keyed_by_id() {
+        let shuffled = input_generator("\
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1");
+        assert_eq!(shuffled[0].id(), 4);
+        assert_eq!(solve_part2(&shuffled), 30);
+        assert_eq!(validate(&shuffled), [
+            CardIssue::OutOfOrder { card: 2, previous: 4 },
+            CardIssue::OutOfOrder { card: 1, previous: 6 },
+            CardIssue::OutOfOrder { card: 3, previous: 5 },
+        ]);
+
+        let sparse = input_generator("Card 1: 1 2 | 1 2\nCard 3: 1 | 1\nCard 3: 5 | 6");
+        assert_eq!(validate(&sparse), [CardIssue::Duplicate(3), CardIssue::Missing(2)]);
+        assert_eq!(Cascade::<usize>::new(&sparse, OutOfRange::Clip).unwrap_err(), CascadeError::Duplicate { card: 3 });
+        assert_eq!(Cascade::<usize>::new(&sparse[..2], OutOfRange::Clip).unwrap().total(), Ok(3));
+        assert_eq!("Card x: 1 | 1".parse::<Card>().unwrap_err(), ParseCardError::InvalidHeader("Card x".to_string()));
+    }
+}
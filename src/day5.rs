@@ -1,35 +1,84 @@
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::Hash;
+use std::str::FromStr;
 use aoc_runner_derive::{aoc, aoc_generator};
+use num::PrimInt;
+use crate::interval_set::IntervalSet;
 
+pub use crate::interval_set::Interval;
+
+pub mod lineage;
+
+/// Integer type almanac ids are stored in, e.g. `i64`, `i128` or `u64`.
+pub trait Id: PrimInt + FromStr + Display + Debug + Hash {}
+
+impl<T: PrimInt + FromStr + Display + Debug + Hash> Id for T {}
+
+/// Part of an interval before a shifter, image of the part inside it and part after it.
+pub type Partition<T> = (Option<Interval<T>>, Option<Interval<T>>, Option<Interval<T>>);
+
+/// Closed interval sent onto `target..=target + (end - start)`
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub struct Interval {
-    start: isize,
-    end: isize,
+pub struct Shifter<T> {
+    pub start: T,
+    pub end: T,
+    pub target: T,
 }
 
-/// Closed interval with shift value
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub struct Shifter {
-    start: isize,
-    end: isize,
-    shift: isize,
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseShifterError {
+    InvalidNumber(String),
+    /// A shifter line holds destination, source and width, got this many numbers instead.
+    WrongArity(usize),
+    NonPositiveWidth(String),
+    /// Source or destination range doesn't fit in the id type.
+    Overflow,
 }
 
-impl Shifter {
-    pub fn from_str(str: &str) -> Self {
-        let mut numbers = str.split_whitespace().map(|sub| sub.parse::<isize>().unwrap());
-        let dst = numbers.next().unwrap();
-        let src = numbers.next().unwrap();
-        let width = numbers.next().unwrap();
-        Self {
-            start: src,
-            end: src + width - 1,
-            shift: dst - src,
+impl Display for ParseShifterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseShifterError::InvalidNumber(raw) => write!(f, "invalid number `{raw}`"),
+            ParseShifterError::WrongArity(n) => write!(f, "expected 3 numbers, got {n}"),
+            ParseShifterError::NonPositiveWidth(width) => write!(f, "width {width} isn't positive"),
+            ParseShifterError::Overflow => write!(f, "range overflows the id type"),
         }
     }
+}
+
+impl Error for ParseShifterError {}
+
+impl<T: Id> FromStr for Shifter<T> {
+    type Err = ParseShifterError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        let numbers = str.split_whitespace()
+            .map(|sub| sub.parse::<T>().map_err(|_| ParseShifterError::InvalidNumber(sub.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [dst, src, width] = numbers[..] else {
+            return Err(ParseShifterError::WrongArity(numbers.len()));
+        };
+        Self::new(dst, src, width)
+    }
+}
+
+impl<T: Id> Shifter<T> {
+    /// Checks that both ranges are non-empty and fit in `T`, so that shifting never overflows.
+    pub fn new(dst: T, src: T, width: T) -> Result<Self, ParseShifterError> {
+        if width <= T::zero() {
+            return Err(ParseShifterError::NonPositiveWidth(width.to_string()));
+        }
+        let last = width - T::one();
+        let end = src.checked_add(&last).ok_or(ParseShifterError::Overflow)?;
+        dst.checked_add(&last).ok_or(ParseShifterError::Overflow)?;
+        Ok(Self { start: src, end, target: dst })
+    }
 
     #[inline]
-    pub fn compare(&self, i: isize) -> Ordering {
+    pub fn compare(&self, i: T) -> Ordering {
         if i < self.start {
             Ordering::Greater
         } else if self.end < i {
@@ -39,16 +88,40 @@ impl Shifter {
         }
     }
 
+    /// Image of `i`, which must lie in the source range.
     #[inline]
-    pub fn shift(&self, i: isize) -> isize {
-        i + self.shift
+    pub fn shift(&self, i: T) -> T {
+        // Identity pieces may be wider than `T` can count, skip the offset.
+        if self.target == self.start {
+            i
+        } else {
+            self.target + (i - self.start)
+        }
     }
 
-    pub fn partition(&self, interval: &Interval) -> (Option<Interval>, Option<Interval>, Option<Interval>) {
+    /// Antecedent of `i`, which must lie in the destination range.
+    #[inline]
+    pub fn unshift(&self, i: T) -> T {
+        if self.target == self.start {
+            i
+        } else {
+            self.start + (i - self.target)
+        }
+    }
+
+    pub fn source(&self) -> Interval<T> {
+        Interval { start: self.start, end: self.end }
+    }
+
+    pub fn destination(&self) -> Interval<T> {
+        Interval { start: self.target, end: self.shift(self.end) }
+    }
+
+    pub fn partition(&self, interval: &Interval<T>) -> Partition<T> {
         let prefix = if interval.start < self.start {
             Some(Interval {
                 start: interval.start,
-                end: interval.end.min(self.start - 1),
+                end: interval.end.min(self.start - T::one()),
             })
         } else {
             None
@@ -58,14 +131,14 @@ impl Shifter {
             None
         } else {
             Some(Interval {
-                start: self.start.max(interval.start) + self.shift,
-                end: self.end.min(interval.end) + self.shift,
+                start: self.shift(self.start.max(interval.start)),
+                end: self.shift(self.end.min(interval.end)),
             })
         };
 
         let suffix = if self.end < interval.end {
             Some(Interval {
-                start: interval.start.max(self.end + 1),
+                start: interval.start.max(self.end + T::one()),
                 end: interval.end,
             })
         } else {
@@ -76,35 +149,124 @@ impl Shifter {
     }
 }
 
-impl Ord for Shifter {
+impl<T: Id> Ord for Shifter<T> {
     #[inline]
     fn cmp(&self, other: &Self) -> Ordering {
         self.start.cmp(&other.start)
     }
 }
 
-impl PartialOrd for Shifter {
+impl<T: Id> PartialOrd for Shifter<T> {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-#[derive(Debug)]
-pub struct Map(Vec<Shifter>);
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Map<T>(Vec<Shifter<T>>);
+
+impl<T> Default for Map<T> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<T: Id> Map<T> {
+    /// Splits the whole id range into consecutive shifters, gaps being sent onto themselves.
+    fn pieces(&self) -> Vec<Shifter<T>> {
+        let mut pieces = vec![];
+        let mut next = T::min_value();
+        for shifter in &self.0 {
+            if next < shifter.start {
+                pieces.push(Shifter { start: next, end: shifter.start - T::one(), target: next });
+            }
+            pieces.push(*shifter);
+            match shifter.end.checked_add(&T::one()) {
+                Some(end) => next = end,
+                None => return pieces,
+            }
+        }
+        pieces.push(Shifter { start: next, end: T::max_value(), target: next });
+        pieces
+    }
+
+    /// Map equivalent to applying `self` then `other`.
+    pub fn compose(&self, other: &Map<T>) -> Map<T> {
+        let other_pieces = other.pieces();
+        let mut shifters = Vec::<Shifter<T>>::new();
+        for piece in self.pieces() {
+            let image = piece.destination();
+            let first = other_pieces.partition_point(|p| p.end < image.start);
+            for other_piece in other_pieces[first..].iter().take_while(|p| p.start <= image.end) {
+                let from = image.start.max(other_piece.start);
+                let to = image.end.min(other_piece.end);
+                let start = piece.unshift(from);
+                let end = piece.unshift(to);
+                let target = other_piece.shift(from);
+                match shifters.last_mut() {
+                    _ if target == start => {}
+                    Some(last) if last.end + T::one() == start
+                        && last.shift(last.end).checked_add(&T::one()) == Some(target) => last.end = end,
+                    _ => shifters.push(Shifter { start, end, target }),
+                }
+            }
+        }
+        Map(shifters)
+    }
 
-impl Map {
     #[inline]
-    pub fn shift_id(&self, i: isize) -> isize {
+    pub fn shift_id(&self, i: T) -> T {
         match self.0.binary_search_by(|s| s.compare(i)) {
             Ok(j) => self.0[j].shift(i),
             Err(_) => i,
         }
     }
 
-    pub fn shift_interval(&self, mut interval: Interval) -> Vec<Interval> {
+    /// Every id mapped to `i`, in increasing order.
+    pub fn preimage_id(&self, i: T) -> Vec<T> {
+        self.pieces()
+            .into_iter()
+            .filter(|piece| piece.destination().contains(i))
+            .map(|piece| piece.unshift(i))
+            .collect()
+    }
+
+    /// Every id mapped into `interval`.
+    pub fn preimage_interval(&self, interval: Interval<T>) -> IntervalSet<T> {
+        self.pieces()
+            .into_iter()
+            .filter_map(|piece| {
+                let image = piece.destination().intersection(&interval)?;
+                Some(Interval { start: piece.unshift(image.start), end: piece.unshift(image.end) })
+            })
+            .collect()
+    }
+
+    /// Inverse map, `None` unless `self` is a bijection, i.e. shifters send their sources onto
+    /// disjoint images covering exactly the same ids.
+    pub fn inverse(&self) -> Option<Map<T>> {
+        let mut inverse = self.0.iter()
+            .map(|s| Shifter { start: s.target, end: s.shift(s.end), target: s.start })
+            .collect::<Vec<_>>();
+        inverse.sort();
+        if inverse.array_windows().any(|[a, b]| b.start <= a.end) {
+            return None;
+        }
+
+        let cover = |shifters: &[Shifter<T>]| shifters.iter().map(Shifter::source).collect::<IntervalSet<_>>();
+        (cover(&self.0) == cover(&inverse)).then_some(Map(inverse))
+    }
+
+    /// Image of a whole set, merged back into minimal intervals.
+    pub fn shift_set(&self, set: &IntervalSet<T>) -> IntervalSet<T> {
+        set.iter().flat_map(|interval| self.shift_interval(*interval)).collect()
+    }
+
+    pub fn shift_interval(&self, mut interval: Interval<T>) -> Vec<Interval<T>> {
         let mut out = vec![];
-        for shifter in self.0.iter().skip_while(move |s| s.end < interval.start) {
+        let first = self.0.partition_point(|s| s.end < interval.start);
+        for shifter in &self.0[first..] {
             let (prefix, intersection, suffix) = shifter.partition(&interval);
             out.extend(prefix.iter());
             out.extend(intersection.iter());
@@ -114,7 +276,7 @@ impl Map {
             }
         }
 
-        if self.0.last().unwrap().end < interval.start {
+        if self.0.last().is_none_or(|last| last.end < interval.start) {
             out.push(interval);
         }
 
@@ -122,49 +284,265 @@ impl Map {
     }
 }
 
-#[aoc_generator(day5)]
-pub fn input_generator(input: &str) -> (Vec<isize>, Vec<Map>) {
-    let mut groups = input.split("\n\n");
-    let seeds = groups.next().unwrap().split_once(": ").unwrap().1.split_whitespace().map(|s| s.parse::<isize>().unwrap()).collect();
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IssueKind {
+    Malformed(ParseShifterError),
+    NonPositiveWidth(String),
+    /// Source range overlapping the one of the shifter at the given line.
+    Overlap { line: usize },
+    /// Destination range overlapping the one of the shifter at the given line.
+    SharedDestination { line: usize },
+    /// Header not of the form `<source>-to-<destination> map:`.
+    InvalidHeader,
+    /// First line not of the form `seeds: <ids>`.
+    InvalidSeeds,
+    InvalidSeed(String),
+    /// Line outside of any map section.
+    OrphanLine,
+    /// Second map between the same categories, the one at the given line being kept.
+    DuplicateMap { line: usize },
+}
 
-    let maps = groups.map(|group| {
-        let mut shifters = group.lines().skip(1).map(Shifter::from_str).collect::<Vec<_>>();
-        shifters.sort();
-        Map(shifters)
-    }).collect();
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Issue {
+    pub map: String,
+    pub line: usize,
+    pub kind: IssueKind,
+}
 
-    (seeds, maps)
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.map.is_empty() {
+            write!(f, "line {}: ", self.line)?;
+        } else {
+            write!(f, "{} map, line {}: ", self.map, self.line)?;
+        }
+        match &self.kind {
+            IssueKind::Malformed(err) => write!(f, "{err}"),
+            IssueKind::NonPositiveWidth(width) => write!(f, "width {width} isn't positive"),
+            IssueKind::Overlap { line } => write!(f, "source overlaps line {line}"),
+            IssueKind::SharedDestination { line } => write!(f, "destination overlaps line {line}"),
+            IssueKind::InvalidHeader => write!(f, "expected `<source>-to-<destination> map:`"),
+            IssueKind::InvalidSeeds => write!(f, "expected `seeds: <ids>`"),
+            IssueKind::InvalidSeed(raw) => write!(f, "invalid seed `{raw}`"),
+            IssueKind::OrphanLine => write!(f, "line outside of any map"),
+            IssueKind::DuplicateMap { line } => write!(f, "same categories as the map at line {line}"),
+        }
+    }
 }
 
-#[aoc(day5, part1)]
-pub fn solve_part1((seeds, maps): &(Vec<isize>, Vec<Map>)) -> isize {
-    seeds.iter().map(|seed| {
-        maps.iter().fold(*seed, |state, map| map.shift_id(state))
-    }).min().unwrap()
+impl Error for Issue {}
+
+/// Whether `parse_almanac` rejects inputs with issues or drops the unusable shifters.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    Strict,
+    Lenient,
 }
 
-#[aoc(day5, part2)]
-pub fn solve_part2((seeds, maps): &(Vec<isize>, Vec<Map>)) -> isize {
-    let mut intervals = seeds.chunks(2)
-        .map(|se| Interval { start: se[0], end: se[0] + se[1] - 1 })
-        .collect::<Vec<_>>();
+/// Reports intervals overlapping an earlier one by start, which is blamed and left out when
+/// checking the following ones, so the intervals not blamed are disjoint.
+fn overlaps<T: Id>(mut intervals: Vec<(Interval<T>, usize)>) -> Vec<(usize, usize)> {
+    intervals.sort_by_key(|(i, _)| i.start);
+    let mut out = vec![];
+    let mut reach: Option<(T, usize)> = None;
+    for (interval, line) in intervals {
+        match reach {
+            Some((end, other)) if interval.start <= end => out.push((line, other)),
+            _ => reach = Some((interval.end, line)),
+        }
+    }
+    out
+}
+
+/// Returns the lines of the shifters whose source overlaps another one.
+fn validate_map<T: Id>(name: &str, shifters: &[(Shifter<T>, usize)], issues: &mut Vec<Issue>) -> Vec<usize> {
+    let issue = |line, kind| Issue { map: name.to_string(), line, kind };
+    let sources = shifters.iter().map(|(s, line)| (s.source(), *line)).collect();
+    let destinations = shifters.iter().map(|(s, line)| (s.destination(), *line)).collect();
+    let overlapping = overlaps(sources);
+    issues.extend(overlapping.iter().map(|(line, other)| issue(*line, IssueKind::Overlap { line: *other })));
+    issues.extend(overlaps(destinations).into_iter().map(|(line, other)| issue(line, IssueKind::SharedDestination { line: other })));
+    overlapping.into_iter().map(|(line, _)| line).collect()
+}
+
+/// Seeds and maps keyed by (source, destination) category, in any order.
+#[derive(Debug, Clone)]
+pub struct Almanac<T> {
+    pub seeds: Vec<T>,
+    maps: BTreeMap<(String, String), Map<T>>,
+}
+
+impl<T: Id> Almanac<T> {
+    /// Seeds read as `start length` pairs, `None` if a range overflows `T`.
+    pub fn seed_ranges(&self) -> Option<IntervalSet<T>> {
+        self.seeds.chunks(2)
+            .filter(|se| se.len() == 2 && se[1] > T::zero())
+            .map(|se| Some(Interval { start: se[0], end: se[0].checked_add(&(se[1] - T::one()))? }))
+            .collect()
+    }
+
+    pub fn map(&self, source: &str, destination: &str) -> Option<&Map<T>> {
+        self.maps.get(&(source.to_string(), destination.to_string()))
+    }
+
+    /// Shortest chain of categories leading from `source` to `destination`, both included.
+    pub fn route<'a>(&'a self, source: &'a str, destination: &'a str) -> Option<Vec<&'a str>> {
+        let mut previous = HashMap::<&str, &str>::new();
+        let mut queue = VecDeque::from([source]);
+        while let Some(category) = queue.pop_front() {
+            if category == destination {
+                let mut route = vec![destination];
+                while let Some(prev) = previous.get(route.last().unwrap()) {
+                    route.push(prev);
+                }
+                route.reverse();
+                return Some(route);
+            }
+            for (src, dst) in self.maps.keys() {
+                if src == category && dst != source && !previous.contains_key(dst.as_str()) {
+                    previous.insert(dst, category);
+                    queue.push_back(dst);
+                }
+            }
+        }
+        None
+    }
+
+    /// Maps to apply in turn to go from `source` to `destination`.
+    pub fn maps_between(&self, source: &str, destination: &str) -> Option<Vec<&Map<T>>> {
+        let route = self.route(source, destination)?;
+        Some(route.array_windows().map(|[src, dst]| self.map(src, dst).unwrap()).collect())
+    }
+
+    /// Single map equivalent to the route from `source` to `destination`.
+    pub fn chain(&self, source: &str, destination: &str) -> Option<Map<T>> {
+        let maps = self.maps_between(source, destination)?;
+        Some(maps.iter().fold(Map::default(), |acc, map| acc.compose(map)))
+    }
 
-    for map in maps {
-        intervals = intervals.iter()
-            .fold(vec![], |mut acc, &interval| {
-                acc.extend(map.shift_interval(interval));
-                acc
-            });
+    pub fn convert(&self, source: &str, destination: &str, id: T) -> Option<T> {
+        let maps = self.maps_between(source, destination)?;
+        Some(maps.iter().fold(id, |id, map| map.shift_id(id)))
     }
+}
+
+/// Parses the seeds and the maps, along with every issue found on the way.
+fn parse_with_issues<T: Id>(input: &str) -> (Almanac<T>, Vec<Issue>) {
+    let mut lines = input.lines().zip(1..);
+    let mut issues = vec![];
+    let mut seeds = vec![];
+    let seeds_issue = |line, kind| Issue { map: "seeds".to_string(), line, kind };
+    match lines.next().map(|(line, number)| (line.strip_prefix("seeds:"), number)) {
+        Some((Some(raw_seeds), number)) => {
+            for raw in raw_seeds.split_whitespace() {
+                match raw.parse() {
+                    Ok(seed) => seeds.push(seed),
+                    Err(_) => issues.push(seeds_issue(number, IssueKind::InvalidSeed(raw.to_string()))),
+                }
+            }
+        }
+        Some((None, number)) => issues.push(seeds_issue(number, IssueKind::InvalidSeeds)),
+        None => issues.push(seeds_issue(1, IssueKind::InvalidSeeds)),
+    }
+
+    // Sections with a bad header are kept, without a name, so their lines aren't reported again.
+    let mut groups = Vec::<(Option<String>, usize, Vec<(Shifter<T>, usize)>)>::new();
+    for (line, number) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_suffix(" map:") {
+            groups.push((Some(name.to_string()), number, vec![]));
+            continue;
+        }
+        if let Some(name) = line.strip_suffix(':') {
+            issues.push(Issue { map: name.to_string(), line: number, kind: IssueKind::InvalidHeader });
+            groups.push((None, number, vec![]));
+            continue;
+        }
+        let Some((name, _, shifters)) = groups.last_mut() else {
+            issues.push(Issue { map: String::new(), line: number, kind: IssueKind::OrphanLine });
+            continue;
+        };
+        let Some(name) = name else {
+            continue;
+        };
+        let issue = |kind| Issue { map: name.clone(), line: number, kind };
+        match line.parse::<Shifter<T>>() {
+            Ok(shifter) => shifters.push((shifter, number)),
+            Err(ParseShifterError::NonPositiveWidth(width)) => issues.push(issue(IssueKind::NonPositiveWidth(width))),
+            Err(err) => issues.push(issue(IssueKind::Malformed(err))),
+        }
+    }
+
+    let mut maps = BTreeMap::new();
+    let mut headers = HashMap::new();
+    for (name, header, shifters) in groups {
+        let Some(name) = name else {
+            continue;
+        };
+        let overlapping = validate_map(&name, &shifters, &mut issues);
+        let Some((source, destination)) = name.split_once("-to-") else {
+            issues.push(Issue { map: name, line: header, kind: IssueKind::InvalidHeader });
+            continue;
+        };
+        let key = (source.to_string(), destination.to_string());
+        if let Some(line) = headers.get(&key) {
+            issues.push(Issue { map: name, line: header, kind: IssueKind::DuplicateMap { line: *line } });
+            continue;
+        }
+        let mut shifters = shifters.into_iter()
+            .filter(|(_, line)| !overlapping.contains(line))
+            .map(|(s, _)| s)
+            .collect::<Vec<_>>();
+        shifters.sort();
+        headers.insert(key.clone(), header);
+        maps.insert(key, Map(shifters));
+    }
+    issues.sort_by_key(|issue| issue.line);
+
+    (Almanac { seeds, maps }, issues)
+}
+
+/// Malformed lines, non-positive widths and overlapping sources or destinations, by map.
+pub fn validate<T: Id>(input: &str) -> Vec<Issue> {
+    parse_with_issues::<T>(input).1
+}
+
+/// In lenient mode, malformed or empty shifters, shifters whose source overlaps an earlier one,
+/// stray lines and maps with bad or duplicate headers are dropped. Shared destinations are kept.
+pub fn parse_almanac<T: Id>(input: &str, mode: Mode) -> Result<Almanac<T>, Vec<Issue>> {
+    let (almanac, issues) = parse_with_issues(input);
+    if mode == Mode::Strict && !issues.is_empty() {
+        Err(issues)
+    } else {
+        Ok(almanac)
+    }
+}
+
+#[aoc_generator(day5)]
+pub fn input_generator(input: &str) -> Almanac<i64> {
+    parse_almanac(input, Mode::Lenient).unwrap()
+}
+
+#[aoc(day5, part1)]
+pub fn solve_part1(almanac: &Almanac<i64>) -> i64 {
+    let map = almanac.chain("seed", "location").unwrap();
+    almanac.seeds.iter().map(|seed| map.shift_id(*seed)).min().unwrap()
+}
 
-    intervals.iter().map(|i| i.start).min().unwrap()
+#[aoc(day5, part2)]
+pub fn solve_part2(almanac: &Almanac<i64>) -> i64 {
+    let map = almanac.chain("seed", "location").unwrap();
+    map.shift_set(&almanac.seed_ranges().unwrap()).min().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{input_generator, solve_part1, solve_part2};
+    use super::*;
 
-    static INPUT: &str = "\
+    pub(super) static INPUT: &str = "\
 seeds: 79 14 55 13
 
 seed-to-soil map:
@@ -208,4 +586,144 @@ humidity-to-location map:
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator(INPUT)), 46);
     }
+
+    #[test]
+    fn composed_map_matches_sequential_maps() {
+        let almanac = input_generator(INPUT);
+        let maps = almanac.maps_between("seed", "location").unwrap();
+        let composed = maps.iter().fold(Map::default(), |acc, map| acc.compose(map));
+
+        assert!(composed.0.array_windows().all(|[a, b]| a.end < b.start));
+        for seed in -10..120 {
+            assert_eq!(composed.shift_id(seed), maps.iter().fold(seed, |state, map| map.shift_id(state)));
+        }
+
+        let identity = Map::default();
+        assert_eq!(identity.shift_interval(Interval { start: 3, end: 7 }), [Interval { start: 3, end: 7 }]);
+        assert_eq!(maps[0].compose(&identity), *maps[0]);
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let almanac = input_generator(INPUT);
+        let maps = almanac.maps_between("seed", "location").unwrap();
+        let composed = maps.iter().fold(Map::default(), |acc, map| acc.compose(map));
+        let inverse = composed.inverse().unwrap();
+        let chain_inverse = maps.iter().rev().fold(Map::default(), |acc, map| acc.compose(&map.inverse().unwrap()));
+
+        assert_eq!(inverse, chain_inverse);
+        assert_eq!(composed.compose(&inverse), Map::default());
+        for seed in -10..120 {
+            let location = composed.shift_id(seed);
+            assert_eq!(inverse.shift_id(location), seed);
+            assert_eq!(composed.preimage_id(location), [seed]);
+        }
+
+        let range = Interval { start: 40, end: 60 };
+        let seeds = composed.preimage_interval(range);
+        for seed in seeds.iter().flat_map(|i| i.start..=i.end) {
+            assert!((range.start..=range.end).contains(&composed.shift_id(seed)));
+        }
+        assert_eq!(seeds.len(), Some(21));
+    }
+
+    #[test]
+    fn non_injective_map_has_no_inverse() {
+        let map = Map(vec!["5 0 3".parse().unwrap()]);
+        assert_eq!(map.inverse(), None);
+        assert_eq!(map.preimage_id(6), [1, 6]);
+        assert_eq!(map.preimage_interval(Interval { start: 0, end: 6 }).intervals(), [
+            Interval { start: 0, end: 1 },
+            Interval { start: 3, end: 6 },
+        ]);
+    }
+
+    #[test]
+    fn validate_reports_map_and_line() {
+        assert!(validate::<i64>(INPUT).is_empty());
+
+        let input = "\
+seeds: 1 x
+
+seed-to-soil map:
+50 98 2
+52 50 48
+60 99 1
+
+soil-to-fertilizer map:
+0 15 0
+0 52 2
+oops";
+        let issues = validate::<i64>(input);
+        assert_eq!(issues, [
+            Issue { map: "seeds".to_string(), line: 1, kind: IssueKind::InvalidSeed("x".to_string()) },
+            Issue { map: "seed-to-soil".to_string(), line: 6, kind: IssueKind::Overlap { line: 4 } },
+            Issue { map: "seed-to-soil".to_string(), line: 6, kind: IssueKind::SharedDestination { line: 5 } },
+            Issue { map: "soil-to-fertilizer".to_string(), line: 9, kind: IssueKind::NonPositiveWidth("0".to_string()) },
+            Issue { map: "soil-to-fertilizer".to_string(), line: 11, kind: IssueKind::Malformed(ParseShifterError::InvalidNumber("oops".to_string())) },
+        ]);
+        assert_eq!(issues[1].to_string(), "seed-to-soil map, line 6: source overlaps line 4");
+
+        assert_eq!(parse_almanac::<i64>(input, Mode::Strict).unwrap_err(), issues);
+        let almanac = parse_almanac::<i64>(input, Mode::Lenient).unwrap();
+        assert_eq!(almanac.seeds, [1]);
+        assert_eq!(almanac.map("seed", "soil").unwrap().0.len(), 2);
+        assert_eq!(almanac.map("soil", "fertilizer").unwrap().0.len(), 1);
+
+        let overlapping = parse_almanac::<i64>("seeds: 7\n\nseed-to-soil map:\n10 0 10\n50 5 2", Mode::Lenient).unwrap();
+        assert_eq!(overlapping.convert("seed", "soil", 7), Some(17));
+
+        let typo = "seeds: 1 2\n\nseed-to-soil:\n1 2 3\n\nsoil-to-location map:\n1 2 3";
+        assert_eq!(validate::<i64>(typo), [Issue { map: "seed-to-soil".to_string(), line: 3, kind: IssueKind::InvalidHeader }]);
+        assert!(parse_almanac::<i64>(typo, Mode::Strict).is_err());
+        assert!(parse_almanac::<i64>(typo, Mode::Lenient).unwrap().map("seed", "soil").is_none());
+
+        let issues = validate::<i64>("seeds 1 2\n1 2 3\n\nsoil-to-location map:\n1 2 3");
+        assert_eq!(issues, [
+            Issue { map: "seeds".to_string(), line: 1, kind: IssueKind::InvalidSeeds },
+            Issue { map: String::new(), line: 2, kind: IssueKind::OrphanLine },
+        ]);
+        assert_eq!(issues[1].to_string(), "line 2: line outside of any map");
+    }
+
+    #[test]
+    fn route_through_shuffled_and_branching_maps() {
+        let mut sections = INPUT.split("\n\n").collect::<Vec<_>>();
+        sections[1..].reverse();
+        sections.push("fertilizer-to-mineral map:\n10 0 5");
+        let almanac = input_generator(&sections.join("\n\n"));
+
+        assert_eq!(solve_part1(&almanac), 35);
+        assert_eq!(solve_part2(&almanac), 46);
+        assert_eq!(almanac.route("soil", "humidity").unwrap(), ["soil", "fertilizer", "water", "light", "temperature", "humidity"]);
+        assert_eq!(almanac.route("seed", "mineral").unwrap(), ["seed", "soil", "fertilizer", "mineral"]);
+        assert_eq!(almanac.convert("seed", "soil", 79), Some(81));
+        assert_eq!(almanac.route("location", "seed"), None);
+
+        let issues = validate::<i64>("seeds: 1\n\nseed-to-soil map:\n1 2 3\n\nseed-to-soil map:\n\nbroken map:");
+        assert_eq!(issues.iter().map(|i| (i.line, i.kind.clone())).collect::<Vec<_>>(), [
+            (6, IssueKind::DuplicateMap { line: 3 }),
+            (8, IssueKind::InvalidHeader),
+        ]);
+    }
+
+    #[test]
+    fn generic_id_widths() {
+        assert_eq!(solve_part2(&input_generator(INPUT)), 46);
+        let unsigned = parse_almanac::<u64>(INPUT, Mode::Strict).unwrap();
+        assert_eq!(unsigned.chain("seed", "location").unwrap().shift_set(&unsigned.seed_ranges().unwrap()).min(), Some(46));
+        let wide = parse_almanac::<i128>(INPUT, Mode::Strict).unwrap();
+        assert_eq!(wide.convert("seed", "location", 79), Some(82));
+
+        let huge = "seeds: 18446744073709551000 100\n\nseed-to-location map:\n0 18446744073709551000 600";
+        let almanac = parse_almanac::<u64>(huge, Mode::Strict).unwrap();
+        assert_eq!(almanac.convert("seed", "location", 18446744073709551010), Some(10));
+        assert_eq!(almanac.map("seed", "location").unwrap().preimage_id(10), [10, 18446744073709551010]);
+
+        assert_eq!("0 250 10".parse::<Shifter<u8>>(), Err(ParseShifterError::Overflow));
+        assert_eq!("2147483640 0 100".parse::<Shifter<i32>>(), Err(ParseShifterError::Overflow));
+        assert_eq!("5 0 -1".parse::<Shifter<i32>>(), Err(ParseShifterError::NonPositiveWidth("-1".to_string())));
+        let overflowing_seeds = parse_almanac::<u8>("seeds: 250 10", Mode::Strict).unwrap();
+        assert_eq!(overflowing_seeds.seed_ranges(), None);
+    }
 }
@@ -0,0 +1,227 @@
+//! Step by step trace of seeds through the maps of an almanac, to debug answers.
+
+use std::fmt::{self, Display, Formatter};
+use super::{Almanac, Id, Interval, Map, Shifter};
+
+/// Value before and after a map, `shifter` being `None` when it went through unchanged.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Step<T> {
+    pub map: String,
+    pub input: T,
+    pub output: T,
+    pub shifter: Option<Shifter<T>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Lineage<T> {
+    pub steps: Vec<Step<T>>,
+}
+
+/// Part of an interval sent through a map by a single shifter, or unchanged.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Fragment<T> {
+    pub input: Interval<T>,
+    pub output: Interval<T>,
+    pub shifter: Option<Shifter<T>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RangeStep<T> {
+    pub map: String,
+    pub fragments: Vec<Fragment<T>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RangeLineage<T> {
+    pub steps: Vec<RangeStep<T>>,
+}
+
+impl<T: Id> Map<T> {
+    pub fn shifter_for(&self, i: T) -> Option<&Shifter<T>> {
+        self.0.binary_search_by(|s| s.compare(i)).ok().map(|j| &self.0[j])
+    }
+
+    /// Like `shift_interval`, keeping track of the shifter applied to each part.
+    pub fn trace_interval(&self, mut interval: Interval<T>) -> Vec<Fragment<T>> {
+        let unchanged = |interval| Fragment { input: interval, output: interval, shifter: None };
+        let mut out = vec![];
+        let first = self.0.partition_point(|s| s.end < interval.start);
+        for shifter in &self.0[first..] {
+            let (prefix, intersection, suffix) = shifter.partition(&interval);
+            out.extend(prefix.map(unchanged));
+            if let Some(output) = intersection {
+                let input = shifter.source().intersection(&interval).unwrap();
+                out.push(Fragment { input, output, shifter: Some(*shifter) });
+            }
+            match suffix {
+                Some(suffix) => interval = suffix,
+                None => return out,
+            }
+        }
+        out.push(unchanged(interval));
+        out
+    }
+}
+
+impl<T: Id> Almanac<T> {
+    fn named_maps<'a>(&'a self, source: &'a str, destination: &'a str) -> Option<Vec<(String, &'a Map<T>)>> {
+        let route = self.route(source, destination)?;
+        Some(route.array_windows()
+            .map(|[src, dst]| (format!("{src}-to-{dst}"), self.map(src, dst).unwrap()))
+            .collect())
+    }
+
+    pub fn lineage(&self, source: &str, destination: &str, id: T) -> Option<Lineage<T>> {
+        let mut input = id;
+        let steps = self.named_maps(source, destination)?
+            .into_iter()
+            .map(|(name, map)| {
+                let output = map.shift_id(input);
+                let step = Step { map: name, input, output, shifter: map.shifter_for(input).copied() };
+                input = output;
+                step
+            })
+            .collect();
+        Some(Lineage { steps })
+    }
+
+    pub fn range_lineage(&self, source: &str, destination: &str, interval: Interval<T>) -> Option<RangeLineage<T>> {
+        let mut inputs = vec![interval];
+        let steps = self.named_maps(source, destination)?
+            .into_iter()
+            .map(|(name, map)| {
+                let fragments = inputs.iter().flat_map(|i| map.trace_interval(*i)).collect::<Vec<_>>();
+                inputs = fragments.iter().map(|f| f.output).collect();
+                RangeStep { map: name, fragments }
+            })
+            .collect();
+        Some(RangeLineage { steps })
+    }
+}
+
+impl<T: Id> Display for Shifter<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..={} -> {}", self.start, self.end, self.target)
+    }
+}
+
+fn describe<T: Id>(shifter: &Option<Shifter<T>>) -> String {
+    shifter.map_or("identity".to_string(), |s| s.to_string())
+}
+
+fn json_string(raw: &str) -> String {
+    format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn json_interval<T: Id>(interval: &Interval<T>) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", interval.start, interval.end)
+}
+
+fn json_shifter<T: Id>(shifter: &Option<Shifter<T>>) -> String {
+    shifter.map_or("null".to_string(), |s| format!("{{\"start\":{},\"end\":{},\"target\":{}}}", s.start, s.end, s.target))
+}
+
+impl<T: Id> Lineage<T> {
+    pub fn to_json(&self) -> String {
+        let steps = self.steps.iter()
+            .map(|s| format!(
+                "{{\"map\":{},\"input\":{},\"output\":{},\"shifter\":{}}}",
+                json_string(&s.map), s.input, s.output, json_shifter(&s.shifter),
+            ))
+            .collect::<Vec<_>>();
+        format!("{{\"steps\":[{}]}}", steps.join(","))
+    }
+}
+
+/// Table with one row per map.
+impl<T: Id> Display for Lineage<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<24} {:>12} {:>12}  shifter", "map", "input", "output")?;
+        for step in &self.steps {
+            write!(f, "\n{:<24} {:>12} {:>12}  {}", step.map, step.input, step.output, describe(&step.shifter))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Id> RangeLineage<T> {
+    pub fn to_json(&self) -> String {
+        let steps = self.steps.iter()
+            .map(|s| {
+                let fragments = s.fragments.iter()
+                    .map(|f| format!(
+                        "{{\"input\":{},\"output\":{},\"shifter\":{}}}",
+                        json_interval(&f.input), json_interval(&f.output), json_shifter(&f.shifter),
+                    ))
+                    .collect::<Vec<_>>();
+                format!("{{\"map\":{},\"fragments\":[{}]}}", json_string(&s.map), fragments.join(","))
+            })
+            .collect::<Vec<_>>();
+        format!("{{\"steps\":[{}]}}", steps.join(","))
+    }
+}
+
+/// Table with one row per fragment, grouped by map.
+impl<T: Id> Display for RangeLineage<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<24} {:>25} {:>25}  shifter", "map", "input", "output")?;
+        for step in &self.steps {
+            for fragment in &step.fragments {
+                write!(
+                    f, "\n{:<24} {:>25} {:>25}  {}",
+                    step.map,
+                    format!("{}..={}", fragment.input.start, fragment.input.end),
+                    format!("{}..={}", fragment.output.start, fragment.output.end),
+                    describe(&fragment.shifter),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{input_generator, parse_almanac, Mode};
+    use super::super::tests::INPUT;
+
+    #[test]
+    fn seed_lineage_match_example() {
+        let lineage = input_generator(INPUT).lineage("seed", "location", 79).unwrap();
+        assert_eq!(lineage.steps.iter().map(|s| s.output).collect::<Vec<_>>(), [81, 81, 81, 74, 78, 78, 82]);
+        assert_eq!(lineage.steps[0].shifter, Some("52 50 48".parse().unwrap()));
+        assert_eq!(lineage.steps[1].shifter, None);
+
+        let table = lineage.to_string();
+        assert_eq!(table.lines().nth(1).unwrap(), format!("{:<24} {:>12} {:>12}  50..=97 -> 52", "seed-to-soil", 79, 81));
+        assert!(table.lines().nth(2).unwrap().ends_with("identity"));
+    }
+
+    #[test]
+    fn range_lineage_and_json() {
+        let almanac = parse_almanac::<i64>("seeds: 0 10\n\nseed-to-soil map:\n100 5 3\n\nsoil-to-location map:\n0 100 1", Mode::Strict).unwrap();
+        assert_eq!(
+            almanac.lineage("seed", "location", 5).unwrap().to_json(),
+            concat!(
+                r#"{"steps":[{"map":"seed-to-soil","input":5,"output":100,"shifter":{"start":5,"end":7,"target":100}},"#,
+                r#"{"map":"soil-to-location","input":100,"output":0,"shifter":{"start":100,"end":100,"target":0}}]}"#,
+            ),
+        );
+
+        let lineage = almanac.range_lineage("seed", "soil", Interval { start: 0, end: 9 }).unwrap();
+        assert_eq!(lineage.steps[0].fragments.iter().map(|f| (f.input, f.output)).collect::<Vec<_>>(), [
+            (Interval { start: 0, end: 4 }, Interval { start: 0, end: 4 }),
+            (Interval { start: 5, end: 7 }, Interval { start: 100, end: 102 }),
+            (Interval { start: 8, end: 9 }, Interval { start: 8, end: 9 }),
+        ]);
+        assert!(lineage.to_json().starts_with(r#"{"steps":[{"map":"seed-to-soil","fragments":[{"input":{"start":0,"end":4},"output":{"start":0,"end":4},"shifter":null},"#));
+        assert_eq!(lineage.to_string().lines().count(), 4);
+
+        let full = input_generator(INPUT).range_lineage("seed", "location", Interval { start: 79, end: 92 }).unwrap();
+        let locations = full.steps.last().unwrap().fragments.iter().map(|f| f.output).collect::<Vec<_>>();
+        let map = input_generator(INPUT).chain("seed", "location").unwrap();
+        assert_eq!(locations.iter().map(|i| i.len().unwrap()).sum::<i64>(), 14);
+        assert!(locations.iter().all(|i| map.shift_set(&[Interval { start: 79, end: 92 }].into_iter().collect()).contains_interval(i)));
+    }
+}
@@ -1,38 +1,301 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use num::{BigInt, BigUint, Integer, Signed, ToPrimitive};
+use num::integer::Roots;
 
-#[aoc_generator(day6, part1)]
-pub fn input_generator1(input: &str) -> Vec<(usize, usize)> {
-    let mut lines = input.lines();
-    let times = lines.next().unwrap().split_whitespace().skip(1).map(|n| n.parse::<usize>().unwrap());
-    let distances = lines.next().unwrap().split_whitespace().skip(1).map(|n| n.parse::<usize>().unwrap());
-    times.zip(distances).collect()
+/// Number of hold times `h` beating `distance`, i.e. such that `h * (time - h) > distance`.
+///
+/// Winning hold times lie strictly between the roots `(time ± sqrt(time² - 4 distance)) / 2`
+/// and are symmetric around `time / 2`, so only the lowest one has to be found. It is
+/// estimated with the integer square root and then nudged to absorb rounding. `N` must be
+/// able to hold `time²`, use `BigUint` for races of any length.
+pub fn ways_to_win<N: Integer + Roots + Clone>(time: &N, distance: &N) -> N {
+    let two = N::one() + N::one();
+    let four = two.clone() * two.clone();
+    let square = time.clone() * time.clone();
+    let threshold = four * distance.clone();
+    if square <= threshold {
+        return N::zero();
+    }
+
+    let beats = |hold: &N| hold.clone() * (time.clone() - hold.clone()) > *distance;
+    let middle = time.clone() / two.clone();
+    let mut low = (time.clone() - (square - threshold).sqrt()) / two.clone();
+    while low <= middle && !beats(&low) {
+        low = low + N::one();
+    }
+    if low > middle {
+        return N::zero();
+    }
+    time.clone() + N::one() - two * low
 }
 
-#[aoc(day6, part1)]
-pub fn solve_part1(input: &[(usize, usize)]) -> usize {
-    input.iter().map(|(time, distance)| {
-        (1..*time).map(|i| i * (time - i)).filter(|c| c > distance).count()
-    }).product()
+/// How a boat moves: holding the button for `h` ms gives it `starting_speed + acceleration * h`
+/// mm/ms, capped at `max_speed`, then each ms of travel loses `drag` mm/ms until it stops.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RaceModel {
+    pub acceleration: u64,
+    pub starting_speed: u64,
+    pub max_speed: Option<u64>,
+    pub drag: Option<u64>,
+}
+
+impl RaceModel {
+    /// Boat from the puzzle statement.
+    pub const fn puzzle() -> Self {
+        Self { acceleration: 1, starting_speed: 0, max_speed: None, drag: None }
+    }
+
+    pub fn speed(&self, hold: u64) -> u64 {
+        let speed = self.starting_speed.saturating_add(self.acceleration.saturating_mul(hold));
+        self.max_speed.map_or(speed, |max| speed.min(max))
+    }
+
+    /// Distance travelled in a race of `time` ms when holding the button for `hold` ms.
+    pub fn distance(&self, hold: u64, time: u64) -> u128 {
+        let speed = self.speed(hold) as u128;
+        let travel = time.saturating_sub(hold) as u128;
+        match self.drag {
+            None | Some(0) => speed * travel,
+            Some(drag) => {
+                // Arithmetic series from `speed` down to `last`, halving whichever factor is even
+                // so the product never exceeds the result.
+                let drag = drag as u128;
+                let moving = travel.min(speed.div_ceil(drag));
+                if moving == 0 {
+                    return 0;
+                }
+                let ends = 2 * speed - drag * (moving - 1);
+                if moving.is_multiple_of(2) { moving / 2 * ends } else { ends / 2 * moving }
+            }
+        }
+    }
+
+    /// First hold time reaching `max_speed`, if any.
+    fn cap_start(&self) -> Option<u64> {
+        let max = self.max_speed?;
+        match max.checked_sub(self.starting_speed) {
+            None | Some(0) => Some(0),
+            Some(_) if self.acceleration == 0 => None,
+            Some(missing) => Some(missing.div_ceil(self.acceleration)),
+        }
+    }
+
+    /// Hold times among `holds` beating `record` at a constant `speed`: `speed * (time - h)` only
+    /// decreases, so they are the ones up to `time - record / speed - 1`.
+    fn constant_speed_holds(speed: u64, time: u64, record: u64, holds: RangeInclusive<u64>) -> Option<RangeInclusive<u64>> {
+        let last = time.checked_sub(record.checked_div(speed)?)?.checked_sub(1)?.min(*holds.end());
+        (*holds.start() <= last).then_some(*holds.start()..=last)
+    }
+
+    /// Hold times among `holds` beating `record` while the boat accelerates, i.e. strictly
+    /// between the roots of `(s + a h) (time - h) - record`. The roots are estimated with the
+    /// integer square root, each winning bound then being one of two integers.
+    fn accelerating_holds(&self, time: u64, record: u64, holds: RangeInclusive<u64>) -> Option<RangeInclusive<u64>> {
+        let (a, s) = (BigInt::from(self.acceleration), BigInt::from(self.starting_speed));
+        let (t, r) = (BigInt::from(time), BigInt::from(record));
+        let beats = |h: &BigInt| (&s + &a * h) * (&t - h) > r;
+
+        let sum = &a * &t + &s;
+        let discriminant = &sum * &sum - BigInt::from(4) * &a * &r;
+        if discriminant.is_negative() {
+            return None;
+        }
+        let floor = discriminant.sqrt();
+        let ceil = if &floor * &floor == discriminant { floor.clone() } else { &floor + 1 };
+        let (b, two_a) = (&a * &t - &s, BigInt::from(2) * &a);
+        let low = (&b - &floor).div_floor(&two_a);
+        let high = (&b + &ceil).div_floor(&two_a);
+
+        let first = if beats(&low) { low } else { low + 1 };
+        let last = if beats(&high) { high } else { high - 1 };
+        let first = first.max(BigInt::from(*holds.start()));
+        let last = last.min(BigInt::from(*holds.end()));
+        (first <= last && beats(&first)).then(|| first.to_u64().unwrap()..=last.to_u64().unwrap())
+    }
+
+    /// Smallest hold time among `holds` satisfying `predicate`, which must switch from false to
+    /// true once over them and hold at their end.
+    fn first_true(holds: RangeInclusive<u64>, predicate: impl Fn(u64) -> bool) -> u64 {
+        let (mut low, mut high) = holds.into_inner();
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if predicate(middle) { high = middle } else { low = middle + 1 }
+        }
+        low
+    }
+
+    /// Distance first grows with the hold time, then shrinks, with flat stretches only at the
+    /// top: the boat stops before the end of the race and is faster the longer it's held, then
+    /// it doesn't stop and the distance is concave, then it's capped and has less time to move.
+    /// The winning hold times are found around the peak by ternary then binary searches.
+    fn dragged_holds(&self, time: u64, record: u64) -> Option<RangeInclusive<u64>> {
+        let distance = |hold| self.distance(hold, time);
+        let (mut low, mut high) = (0, time);
+        while high - low > 2 {
+            let (left, right) = (low + (high - low) / 3, high - (high - low) / 3);
+            if distance(left) < distance(right) { low = left + 1 } else { high = right }
+        }
+        let peak = (low..=high).max_by_key(|hold| distance(*hold)).unwrap();
+        let beats = |hold| distance(hold) > record as u128;
+        if !beats(peak) {
+            return None;
+        }
+        let first = Self::first_true(0..=peak, beats);
+        let last = time - Self::first_true(0..=time - peak, |back| beats(time - back));
+        Some(first..=last)
+    }
+
+    /// Maximal runs of hold times beating `record`, in increasing order.
+    ///
+    /// Without drag, distance is quadratic in the hold time while accelerating and linear once
+    /// capped, both pieces being solved in closed form. With drag, the bounds are searched for.
+    /// Either way, the winning hold times form a single run.
+    pub fn winning_holds(&self, time: u64, record: u64) -> Vec<RangeInclusive<u64>> {
+        if self.drag.is_some_and(|drag| drag > 0) {
+            return self.dragged_holds(time, record).into_iter().collect();
+        }
+
+        let cap = self.cap_start().filter(|cap| *cap <= time);
+        let mut runs = vec![];
+        if cap != Some(0) {
+            let holds = 0..=cap.map_or(time, |cap| cap - 1);
+            runs.extend(match self.acceleration {
+                0 => Self::constant_speed_holds(self.starting_speed, time, record, holds),
+                _ => self.accelerating_holds(time, record, holds),
+            });
+        }
+        if let (Some(cap), Some(max)) = (cap, self.max_speed) {
+            let capped = Self::constant_speed_holds(max, time, record, cap..=time);
+            match (runs.last_mut(), capped) {
+                (Some(run), Some(capped)) if *run.end() + 1 == *capped.start() => *run = *run.start()..=*capped.end(),
+                (_, capped) => runs.extend(capped),
+            }
+        }
+        runs
+    }
+
+    pub fn ways_to_win(&self, time: u64, record: u64) -> u64 {
+        self.winning_holds(time, record).iter().map(|run| run.end() - run.start() + 1).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Race<N = u64> {
+    pub time: N,
+    pub record: N,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseSheetError {
+    MissingLine(&'static str),
+    NoColumns { line: usize, label: &'static str },
+    InvalidLabel { line: usize, expected: &'static str, found: String },
+    InvalidNumber { line: usize, raw: String },
+    ColumnMismatch { times: usize, records: usize },
+    TrailingLine(usize),
+}
+
+impl Display for ParseSheetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseSheetError::MissingLine(label) => write!(f, "missing `{label}` line"),
+            ParseSheetError::NoColumns { line, label } => write!(f, "line {line}: no value after `{label}`"),
+            ParseSheetError::InvalidLabel { line, expected, found } => write!(f, "line {line}: expected `{expected}`, got `{found}`"),
+            ParseSheetError::InvalidNumber { line, raw } => write!(f, "line {line}: invalid number `{raw}`"),
+            ParseSheetError::ColumnMismatch { times, records } => write!(f, "{times} times but {records} distances"),
+            ParseSheetError::TrailingLine(line) => write!(f, "line {line}: unexpected content after the distances"),
+        }
+    }
+}
+
+impl Error for ParseSheetError {}
+
+/// Race sheet read both as one race per column and as a single race with the spaces removed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RaceSheet {
+    races: Vec<Race>,
+    joined: Race<BigUint>,
+}
+
+impl FromStr for RaceSheet {
+    type Err = ParseSheetError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut lines = input.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let (times, joined_time) = Self::parse_line(lines.next(), "Time:")?;
+        let (records, joined_record) = Self::parse_line(lines.next(), "Distance:")?;
+        if let Some((n, _)) = lines.next() {
+            return Err(ParseSheetError::TrailingLine(n + 1));
+        }
+        if times.len() != records.len() {
+            return Err(ParseSheetError::ColumnMismatch { times: times.len(), records: records.len() });
+        }
+        Ok(Self {
+            races: times.into_iter().zip(records).map(|(time, record)| Race { time, record }).collect(),
+            joined: Race { time: joined_time, record: joined_record },
+        })
+    }
 }
 
-#[aoc_generator(day6, part2)]
-pub fn input_generator2(input: &str) -> (usize, usize) {
-    let mut lines = input.lines();
-    let time = lines.next().unwrap().split_whitespace().skip(1).collect::<Vec<_>>().join("").parse::<usize>().unwrap();
-    let distance = lines.next().unwrap().split_whitespace().skip(1).collect::<Vec<_>>().join("").parse::<usize>().unwrap();
+impl RaceSheet {
+    fn parse_line(line: Option<(usize, &str)>, label: &'static str) -> Result<(Vec<u64>, BigUint), ParseSheetError> {
+        let (n, line) = line.ok_or(ParseSheetError::MissingLine(label))?;
+        let line_number = n + 1;
+        let values = line.strip_prefix(label).ok_or_else(|| ParseSheetError::InvalidLabel {
+            line: line_number,
+            expected: label,
+            found: line.split_whitespace().next().unwrap_or_default().to_string(),
+        })?;
 
-    (time, distance)
+        let columns = values.split_whitespace()
+            .map(|raw| raw.bytes().all(|b| b.is_ascii_digit())
+                .then(|| raw.parse().ok())
+                .flatten()
+                .ok_or_else(|| ParseSheetError::InvalidNumber { line: line_number, raw: raw.to_string() }))
+            .collect::<Result<Vec<u64>, _>>()?;
+        if columns.is_empty() {
+            return Err(ParseSheetError::NoColumns { line: line_number, label });
+        }
+        let joined = values.split_whitespace().collect::<String>().parse().unwrap();
+        Ok((columns, joined))
+    }
+
+    pub fn races(&self) -> &[Race] {
+        &self.races
+    }
+
+    /// The single race obtained by ignoring the spaces between columns, of any length.
+    pub fn joined(&self) -> &Race<BigUint> {
+        &self.joined
+    }
+}
+
+#[aoc_generator(day6)]
+pub fn input_generator(input: &str) -> RaceSheet {
+    input.parse().unwrap()
+}
+
+#[aoc(day6, part1)]
+pub fn solve_part1(input: &RaceSheet) -> u64 {
+    input.races().iter().map(|race| RaceModel::puzzle().ways_to_win(race.time, race.record)).product()
 }
 
 #[aoc(day6, part2)]
-pub fn solve_part2((time, distance): &(usize, usize)) -> usize {
-    (1..*time).map(|i| i * (time - i)).filter(|c| c > distance).count()
+pub fn solve_part2(input: &RaceSheet) -> BigUint {
+    let race = input.joined();
+    ways_to_win(&race.time, &race.record)
 }
 
 
 #[cfg(test)]
 mod tests {
-    use super::{input_generator1, solve_part1, input_generator2, solve_part2};
+    use num::BigUint;
+    use std::ops::RangeInclusive;
+    use super::{input_generator, solve_part1, solve_part2, ways_to_win, ParseSheetError, Race, RaceModel, RaceSheet};
 
     static INPUT: &str = "\
 Time:      7  15   30
@@ -40,11 +303,127 @@ Distance:  9  40  200";
 
     #[test]
     fn solver_part1_match_example() {
-        assert_eq!(solve_part1(&input_generator1(INPUT)), 288);
+        assert_eq!(solve_part1(&input_generator(INPUT)), 288);
     }
 
     #[test]
     fn solver_part2_match_example() {
-        assert_eq!(solve_part2(&input_generator2(INPUT)), 71503);
+        assert_eq!(solve_part2(&input_generator(INPUT)), BigUint::from(71503u32));
+    }
+
+    #[test]
+    fn closed_form_matches_enumeration() {
+        for time in 0..60u64 {
+            for distance in 0..(time * time / 4 + 2) {
+                let expected = (1..time).filter(|i| i * (time - i) > distance).count() as u64;
+                assert_eq!(ways_to_win(&time, &distance), expected, "time {time}, distance {distance}");
+            }
+        }
+    }
+
+    #[test]
+    fn closed_form_handles_huge_races() {
+        // The lowest winning hold time must beat the record and the one before it must not.
+        let time = BigUint::from(10u32).pow(40) + BigUint::from(1u32);
+        let distance = BigUint::from(10u32).pow(78) + BigUint::from(12345u32);
+        let ways = ways_to_win(&time, &distance);
+        let low = (&time + 1u32 - &ways) / 2u32;
+        assert!(&low * (&time - &low) > distance);
+        assert!((&low - 1u32) * (&time - &low + 1u32) <= distance);
+
+        let (time, distance) = (10u128.pow(18) + 1, 10u128.pow(34) + 12345);
+        let ways = ways_to_win(&time, &distance);
+        let low = (time + 1 - ways) / 2;
+        assert!(low * (time - low) > distance);
+        assert!((low - 1) * (time - low + 1) <= distance);
+    }
+
+    #[test]
+    fn race_models_winning_holds() {
+        let puzzle = RaceModel::puzzle();
+        assert_eq!(puzzle.winning_holds(7, 9), [2..=5]);
+        assert_eq!(puzzle.winning_holds(30, 200), [11..=19]);
+        assert_eq!(puzzle.winning_holds(3, 2), []);
+
+        let capped = RaceModel { max_speed: Some(3), ..puzzle };
+        assert_eq!(capped.winning_holds(7, 9), [2..=3]);
+        let rolling = RaceModel { starting_speed: 1, ..puzzle };
+        assert_eq!(rolling.winning_holds(7, 9), [1..=5]);
+        let dragged = RaceModel { drag: Some(1), ..puzzle };
+        assert_eq!(dragged.distance(3, 7), 6);
+        assert_eq!(dragged.winning_holds(7, 8), [4..=5]);
+        assert_eq!(dragged.ways_to_win(7, 9), 0);
+
+        let (time, record) = (5_000_000_000, 1);
+        assert_eq!(puzzle.ways_to_win(time, record), time - 1);
+        assert_eq!(RaceModel { max_speed: Some(u64::MAX), ..puzzle }.winning_holds(time, record), [1..=time - 1]);
+
+        let fast = RaceModel { acceleration: 2, ..puzzle };
+        assert_eq!(fast.ways_to_win(200_000_000, 1), 200_000_000 - 1);
+        let (dragged, time, record) = (RaceModel { drag: Some(3), ..fast }, 10u64.pow(18), 10u64.pow(18));
+        let holds = dragged.winning_holds(time, record);
+        let (first, last) = (*holds[0].start(), *holds[0].end());
+        assert!(dragged.distance(first, time) > record as u128 && dragged.distance(first - 1, time) <= record as u128);
+        assert!(dragged.distance(last, time) > record as u128 && dragged.distance(last + 1, time) <= record as u128);
+
+        let slow = RaceModel { acceleration: 2, ..puzzle };
+        for time in 0..40 {
+            for record in 0..(time * time / 4 + 2) {
+                let shortcut = puzzle.winning_holds(time, record);
+                let scanned = slow.winning_holds(time, 2 * record + 1);
+                assert_eq!(shortcut, scanned, "time {time}, record {record}");
+            }
+        }
+    }
+
+    #[test]
+    fn race_models_match_enumeration() {
+        for acceleration in 0..4 {
+            for starting_speed in 0..3 {
+                for max_speed in [None, Some(0), Some(2), Some(5)] {
+                    for drag in [None, Some(1), Some(2), Some(7)] {
+                        let model = RaceModel { acceleration, starting_speed, max_speed, drag };
+                        for time in 0..25 {
+                            for record in (0..60).step_by(3) {
+                                let mut expected: Vec<RangeInclusive<u64>> = vec![];
+                                for hold in (0..=time).filter(|hold| model.distance(*hold, time) > record as u128) {
+                                    match expected.last_mut() {
+                                        Some(run) if *run.end() + 1 == hold => *run = *run.start()..=hold,
+                                        _ => expected.push(hold..=hold),
+                                    }
+                                }
+                                assert_eq!(model.winning_holds(time, record), expected, "{model:?}, time {time}, record {record}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn race_sheet_views_and_errors() {
+        let long = "Time: 99999 99999\nDistance: 1 1".parse::<RaceSheet>().unwrap();
+        assert_eq!(solve_part2(&long), BigUint::from(9999999998u64));
+
+        let sheet = input_generator(INPUT);
+        assert_eq!(sheet.races()[1], Race { time: 15, record: 40 });
+        assert_eq!(*sheet.joined(), Race { time: BigUint::from(71530u32), record: BigUint::from(940200u32) });
+
+        assert_eq!("Time: 7 15\nDistance: 9".parse::<RaceSheet>(), Err(ParseSheetError::ColumnMismatch { times: 2, records: 1 }));
+        assert_eq!("Time: 7\nDistnace: 9".parse::<RaceSheet>(), Err(ParseSheetError::InvalidLabel { line: 2, expected: "Distance:", found: "Distnace:".to_string() }));
+        assert_eq!("Time: 7".parse::<RaceSheet>(), Err(ParseSheetError::MissingLine("Distance:")));
+        assert_eq!("Time:\nDistance: 9".parse::<RaceSheet>(), Err(ParseSheetError::NoColumns { line: 1, label: "Time:" }));
+        assert_eq!("Time: 7 x\nDistance: 9 1".parse::<RaceSheet>(), Err(ParseSheetError::InvalidNumber { line: 1, raw: "x".to_string() }));
+        assert_eq!("Time: 7\nDistance: 9\nTime: 1".parse::<RaceSheet>(), Err(ParseSheetError::TrailingLine(3)));
+        assert_eq!("Time: +7\nDistance: 9".parse::<RaceSheet>(), Err(ParseSheetError::InvalidNumber { line: 1, raw: "+7".to_string() }));
+
+        let huge = "Time: 18446744073709551615 18446744073709551615\nDistance: 1 1".parse::<RaceSheet>().unwrap();
+        assert_eq!(huge.joined().time.to_string(), "1844674407370955161518446744073709551615");
+        assert_eq!(solve_part2(&huge), &huge.joined().time - 1u32);
+        assert_eq!(
+            "Time: 7\nDistnace: 9".parse::<RaceSheet>().unwrap_err().to_string(),
+            "line 2: expected `Distance:`, got `Distnace:`",
+        );
     }
 }
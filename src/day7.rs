@@ -1,165 +1,134 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::marker::PhantomData;
 use aoc_runner_derive::{aoc, aoc_generator};
 
-fn hand_value(cards: &[u8]) -> usize {
-    cards.iter().fold(HashMap::<u8, usize>::new(), |mut acc, c| {
-        acc.entry(*c).and_modify(|i| *i += 1).or_insert(1usize);
-        acc
-    }).values().fold(0, |mut acc, v| {
-        acc += v.pow(2);
-        acc
-    })
-}
-
-fn card_value(card: &u8) -> usize {
-    match card {
-        b'A' => 14,
-        b'K' => 13,
-        b'Q' => 12,
-        b'J' => 11,
-        b'T' => 10,
-        c => (c - b'0').into(),
-    }
-}
+/// Rules of a Camel Cards variant.
+pub trait Ruleset {
+    /// Cards from the weakest to the strongest.
+    const ORDER: &'static [u8];
+    /// Cards standing for whichever card makes the best hand.
+    const WILDCARDS: &'static [u8] = &[];
 
-fn joker_hand_value(cards: &[u8]) -> usize {
-    let mut groups = cards.iter().fold(HashMap::<u8, usize>::new(), |mut acc, c| {
-        acc.entry(*c).and_modify(|i| *i += 1).or_insert(1usize);
-        acc
-    });
+    fn card_value(card: u8) -> usize {
+        Self::ORDER.iter().position(|c| *c == card).unwrap()
+    }
 
-    if let Some(jokers) = groups.remove(&b'J') {
-        if let Some(max_count) = groups.values().max() {
-            let figures = groups.iter().filter(|(_, v)| *v == max_count).map(|(k, _)| *k).collect::<Vec<_>>();
-            let figure = figures.iter().max_by_key(|v| joker_card_value(v)).unwrap();
-            groups.entry(*figure).and_modify(|v| *v += jokers);
-        } else {
-            groups.insert(b'J', 5);
+    /// Strength of the hand type, as the sum of the squared group sizes (five of a kind: 25,
+    /// four of a kind: 17, ..., high card: 5). Wildcards join the largest group.
+    fn hand_value(cards: &[u8]) -> usize {
+        let mut groups = cards.iter().filter(|c| !Self::WILDCARDS.contains(c)).fold(HashMap::<u8, usize>::new(), |mut acc, c| {
+            acc.entry(*c).and_modify(|i| *i += 1).or_insert(1usize);
+            acc
+        }).into_values().collect::<Vec<_>>();
+        groups.sort_unstable();
+
+        let wildcards = cards.len() - groups.iter().sum::<usize>();
+        match groups.last_mut() {
+            Some(largest) => *largest += wildcards,
+            None => groups.push(wildcards),
         }
+
+        groups.iter().map(|v| v.pow(2)).sum()
     }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Standard;
 
-    groups.values().fold(0, |mut acc, v| {
-        acc += v.pow(2);
-        acc
-    })
+impl Ruleset for Standard {
+    const ORDER: &'static [u8] = b"23456789TJQKA";
 }
 
-fn joker_card_value(card: &u8) -> usize {
-    match card {
-        b'J' => 1,
-        b'A' => 14,
-        b'K' => 13,
-        b'Q' => 12,
-        b'T' => 10,
-        c => (c - b'0').into(),
-    }
+/// `J` cards are jokers: wild, but the weakest card on ties.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Jokers;
+
+impl Ruleset for Jokers {
+    const ORDER: &'static [u8] = b"J23456789TQKA";
+    const WILDCARDS: &'static [u8] = b"J";
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Hand {
+#[derive(Debug)]
+pub struct Hand<R> {
     cards: Vec<u8>,
     bid: usize,
     value: usize,
+    rules: PhantomData<R>,
 }
 
-impl Hand {
-    pub fn new(cards: String, bid: usize) -> Self {
-        let cards = cards.bytes().collect::<Vec<_>>();
-        Self {
-            value: hand_value(&cards),
-            cards,
-            bid,
-        }
-    }
-}
-
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.value.cmp(&other.value) {
-            Ordering::Equal => {
-                let (a, b) = self.cards.iter().zip(other.cards.iter())
-                    .map(|(a, b)| (card_value(a), card_value(b)))
-                    .find(|(a, b)| a != b).unwrap();
-                a.cmp(&b)
-            },
-            ord => ord,
-        }
+impl<R> Clone for Hand<R> {
+    fn clone(&self) -> Self {
+        Self { cards: self.cards.clone(), bid: self.bid, value: self.value, rules: PhantomData }
     }
 }
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+impl<R> PartialEq for Hand<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cards == other.cards && self.bid == other.bid
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct HandJoker {
-    cards: Vec<u8>,
-    bid: usize,
-    value: usize,
-}
+impl<R> Eq for Hand<R> {}
 
-impl HandJoker {
+impl<R: Ruleset> Hand<R> {
     pub fn new(cards: String, bid: usize) -> Self {
         let cards = cards.bytes().collect::<Vec<_>>();
         Self {
-            value: joker_hand_value(&cards),
+            value: R::hand_value(&cards),
             cards,
             bid,
+            rules: PhantomData,
         }
     }
 }
 
-impl Ord for HandJoker {
+impl<R: Ruleset> Ord for Hand<R> {
     fn cmp(&self, other: &Self) -> Ordering {
-        match self.value.cmp(&other.value) {
-            Ordering::Equal => {
-                let (a, b) = self.cards.iter().zip(other.cards.iter())
-                    .map(|(a, b)| (joker_card_value(a), joker_card_value(b)))
-                    .find(|(a, b)| a != b).unwrap();
-                a.cmp(&b)
-            },
-            ord => ord,
-        }
+        self.value.cmp(&other.value).then_with(|| {
+            self.cards.iter().map(|c| R::card_value(*c))
+                .cmp(other.cards.iter().map(|c| R::card_value(*c)))
+        })
     }
 }
 
-impl PartialOrd for HandJoker {
+impl<R: Ruleset> PartialOrd for Hand<R> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-#[aoc_generator(day7, part1)]
-pub fn input_generator1(input: &str) -> Vec<Hand> {
+pub fn parse_hands<R: Ruleset>(input: &str) -> Vec<Hand<R>> {
     input.split_whitespace()
         .array_chunks()
         .map(|[cards, value]| Hand::new(cards.to_string(), value.parse().unwrap()))
         .collect()
 }
 
-#[aoc(day7, part1)]
-pub fn solve_part1(input: &[Hand]) -> usize {
+pub fn total_winnings<R: Ruleset>(input: &[Hand<R>]) -> usize {
     let mut hands = input.to_vec();
     hands.sort();
     hands.iter().enumerate().map(|(k, hand)| (k + 1) * hand.bid).sum()
 }
 
+#[aoc_generator(day7, part1)]
+pub fn input_generator1(input: &str) -> Vec<Hand<Standard>> {
+    parse_hands(input)
+}
+
+#[aoc(day7, part1)]
+pub fn solve_part1(input: &[Hand<Standard>]) -> usize {
+    total_winnings(input)
+}
+
 #[aoc_generator(day7, part2)]
-pub fn input_generator2(input: &str) -> Vec<HandJoker> {
-    input.split_whitespace()
-        .array_chunks()
-        .map(|[cards, value]| HandJoker::new(cards.to_string(), value.parse().unwrap()))
-        .collect()
+pub fn input_generator2(input: &str) -> Vec<Hand<Jokers>> {
+    parse_hands(input)
 }
 
 #[aoc(day7, part2)]
-pub fn solve_part2(input: &[HandJoker]) -> usize {
-    let mut hands = input.to_vec();
-    hands.sort();
-    hands.iter().enumerate().map(|(k, hand)| (k + 1) * hand.bid).sum()
+pub fn solve_part2(input: &[Hand<Jokers>]) -> usize {
+    total_winnings(input)
 }
 
 #[cfg(test)]
@@ -182,4 +151,27 @@ QQQJA 483";
     fn solver_part2_match_example() {
         assert_eq!(solve_part2(&input_generator2(INPUT)), 5905);
     }
+
+    #[test]
+    fn hand_values_per_ruleset() {
+        assert_eq!(Standard::hand_value(b"KTJJT"), 9);
+        assert_eq!(Jokers::hand_value(b"KTJJT"), 17);
+        assert_eq!(Jokers::hand_value(b"JJJJJ"), 25);
+        assert_eq!(Jokers::hand_value(b"23456"), 5);
+        assert!(Hand::<Jokers>::new("JKKK2".to_string(), 0) > Hand::<Jokers>::new("QKKK2".to_string(), 0));
+        assert!(Hand::<Standard>::new("JKKK2".to_string(), 0) < Hand::<Standard>::new("QKKK2".to_string(), 0));
+    }
+
+    #[test]
+    fn custom_ruleset() {
+        struct DeucesWild;
+
+        impl Ruleset for DeucesWild {
+            const ORDER: &'static [u8] = b"2J3456789TQKA";
+            const WILDCARDS: &'static [u8] = b"J2";
+        }
+
+        assert_eq!(DeucesWild::hand_value(b"32T3K"), 11);
+        assert_eq!(total_winnings(&parse_hands::<DeucesWild>(INPUT)), 28 + 2 * 765 + 3 * 684 + 4 * 483 + 5 * 220);
+    }
 }
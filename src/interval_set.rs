@@ -0,0 +1,205 @@
+use num::PrimInt;
+
+/// Closed integer interval, `start` and `end` included.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Interval<T> {
+    pub start: T,
+    pub end: T,
+}
+
+impl<T: PrimInt> Interval<T> {
+    pub fn new(start: T, end: T) -> Self {
+        Self { start, end }
+    }
+
+    /// Number of integers in the interval, `None` if it doesn't fit in `T`, as for the full
+    /// range of the type.
+    pub fn len(&self) -> Option<T> {
+        self.end.checked_sub(&self.start)?.checked_add(&T::one())
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (start <= end).then_some(Self { start, end })
+    }
+
+    /// Whether both intervals overlap or are adjacent, `self` starting first.
+    fn touches(&self, next: &Self) -> bool {
+        next.start <= self.end || self.end.checked_add(&T::one()) == Some(next.start)
+    }
+}
+
+/// Set of integers stored as sorted, disjoint and non-adjacent closed intervals.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: PrimInt> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self { intervals: vec![] }
+    }
+
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&Interval<T>> {
+        self.intervals.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of integers in the set, `None` if it doesn't fit in `T`.
+    pub fn len(&self) -> Option<T> {
+        self.intervals.iter().try_fold(T::zero(), |acc, i| acc.checked_add(&i.len()?))
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.intervals.first().map(|i| i.start)
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.intervals.last().map(|i| i.end)
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        let i = self.intervals.partition_point(|i| i.end < value);
+        self.intervals.get(i).is_some_and(|i| i.contains(value))
+    }
+
+    pub fn contains_interval(&self, interval: &Interval<T>) -> bool {
+        let i = self.intervals.partition_point(|i| i.end < interval.start);
+        self.intervals.get(i).is_some_and(|i| i.start <= interval.start && interval.end <= i.end)
+    }
+
+    pub fn insert(&mut self, interval: Interval<T>) {
+        if interval.end < interval.start {
+            return;
+        }
+        let first = self.intervals.partition_point(|i| !i.touches(&interval));
+        let mut merged = interval;
+        let mut last = first;
+        while last < self.intervals.len() && merged.touches(&self.intervals[last]) {
+            merged.start = merged.start.min(self.intervals[last].start);
+            merged.end = merged.end.max(self.intervals[last].end);
+            last += 1;
+        }
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.iter().chain(other.iter()).copied().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut out = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (&self.intervals[i], &other.intervals[j]);
+            out.extend(a.intersection(b));
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { intervals: out }
+    }
+
+    /// Integers of `bounds` that aren't in the set.
+    pub fn complement(&self, bounds: Interval<T>) -> Self {
+        let mut out = Self::new();
+        let mut next = Some(bounds.start);
+        for interval in self.iter() {
+            let Some(start) = next else { break };
+            if start < interval.start {
+                out.insert(Interval::new(start, (interval.start - T::one()).min(bounds.end)));
+            }
+            next = interval.end.checked_add(&T::one()).map(|n| n.max(start));
+        }
+        if let Some(start) = next {
+            out.insert(Interval::new(start, bounds.end));
+        }
+        out
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        match (self.min(), self.max()) {
+            (Some(min), Some(max)) => self.intersection(&other.complement(Interval::new(min, max))),
+            _ => Self::new(),
+        }
+    }
+}
+
+impl<T: PrimInt> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item=Interval<T>>>(iter: I) -> Self {
+        let mut intervals = iter.into_iter().filter(|i| i.start <= i.end).collect::<Vec<_>>();
+        intervals.sort_by_key(|i| i.start);
+        let mut out = Vec::<Interval<T>>::with_capacity(intervals.len());
+        for interval in intervals {
+            match out.last_mut() {
+                Some(last) if last.touches(&interval) => last.end = last.end.max(interval.end),
+                _ => out.push(interval),
+            }
+        }
+        Self { intervals: out }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(intervals: &[(i64, i64)]) -> IntervalSet<i64> {
+        intervals.iter().map(|(start, end)| Interval::new(*start, *end)).collect()
+    }
+
+    #[test]
+    fn normalises_on_construction_and_insert() {
+        let mut s = set(&[(5, 8), (1, 2), (3, 3), (10, 12), (7, 9)]);
+        assert_eq!(s, set(&[(1, 3), (5, 12)]));
+        assert_eq!(s.intervals().len(), 2);
+        assert_eq!(s.len(), Some(11));
+
+        s.insert(Interval::new(4, 4));
+        assert_eq!(s.intervals(), [Interval::new(1, 12)]);
+        s.insert(Interval::new(20, 19));
+        assert_eq!(s.intervals(), [Interval::new(1, 12)]);
+
+        let mut edges = IntervalSet::<u8>::new();
+        edges.insert(Interval::new(0, 10));
+        edges.insert(Interval::new(250, 255));
+        edges.insert(Interval::new(11, 249));
+        assert_eq!(edges.intervals(), [Interval::new(0, 255)]);
+        assert_eq!(edges.len(), None);
+        assert_eq!(Interval::new(i8::MIN, i8::MAX).len(), None);
+        assert_eq!(Interval::new(0u8, 254).len(), Some(255));
+    }
+
+    #[test]
+    fn set_algebra() {
+        let a = set(&[(1, 5), (10, 15), (20, 25)]);
+        let b = set(&[(4, 11), (14, 21)]);
+
+        assert_eq!(a.union(&b), set(&[(1, 25)]));
+        assert_eq!(a.intersection(&b), set(&[(4, 5), (10, 11), (14, 15), (20, 21)]));
+        assert_eq!(a.difference(&b), set(&[(1, 3), (12, 13), (22, 25)]));
+        assert_eq!(a.complement(Interval::new(0, 30)), set(&[(0, 0), (6, 9), (16, 19), (26, 30)]));
+        assert_eq!(a.complement(Interval::new(3, 12)), set(&[(6, 9)]));
+        assert_eq!(set(&[(i64::MIN, i64::MAX)]).complement(Interval::new(0, 5)), IntervalSet::new());
+
+        assert!(a.contains(12));
+        assert!(!a.contains(7));
+        assert!(a.contains_interval(&Interval::new(21, 24)));
+        assert!(!a.contains_interval(&Interval::new(4, 10)));
+        assert_eq!((a.min(), a.max()), (Some(1), Some(25)));
+    }
+}
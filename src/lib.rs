@@ -1,17 +1,19 @@
-#![feature(iter_array_chunks, array_windows)]
+#![feature(iter_array_chunks)]
 
 use aoc_runner_derive::aoc_lib;
 
-mod day1;
-mod day2;
-mod day3;
-mod day4;
-mod day5;
-mod day6;
-mod day7;
+pub mod day1;
+pub mod day2;
+pub mod day3;
+pub mod day4;
+pub mod day5;
+pub mod day6;
+pub mod day7;
 mod day8;
 mod day9;
 mod day10;
 mod day11;
 
+pub mod interval_set;
+
 aoc_lib! { year = 2023 }